@@ -3,7 +3,7 @@ use std::{str::FromStr, time::Duration};
 use chess::Board;
 use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
 
-use patch::engine::evaluation::eval_heuristic;
+use patch::engine::evaluation::{EvalParams, eval_heuristic};
 
 const FENS: [&str; 6] = [
     "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
@@ -27,7 +27,7 @@ fn criterion_benchmark(c: &mut Criterion) {
 
     for board in boards.iter() {
         group.bench_with_input(BenchmarkId::from_parameter(board), board, |b, board| {
-            b.iter(|| eval_heuristic(board));
+            b.iter(|| eval_heuristic(board, &EvalParams::DEFAULT));
         });
     }
 }
@@ -0,0 +1,130 @@
+use std::time::Duration;
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use uci_parser::UciSearchOptions;
+
+use patch::engine::{Engine, PruningConfig};
+
+/// Middlegame positions, where move ordering matters the most
+const FENS: [&str; 4] = [
+    "rnb1k1nr/pp3ppp/2pp1q2/4p3/2BbP3/2N2N2/PPP2PPP/R1BQ1RK1 w kq - 0 1",
+    "r1b2rk1/pp1n1p1p/1bp3qp/3p4/4p3/1QP2NNP/PP2BPPK/R4R2 w - - 0 1",
+    "4rr1k/pp3p1p/1b2n2p/3p1q2/1Q6/2P1pPPP/PP2B2K/2R2R2 b - - 0 1",
+    "r2q1rk1/pp2bppp/2n1bn2/3p4/3P4/2NBBN2/PP3PPP/R2Q1RK1 w - - 0 1",
+];
+
+/// Positions with lots of captures available, where the quiescence search does most of the work
+const CAPTURE_FENS: [&str; 3] = [
+    "r1bqkb1r/ppp2ppp/2n5/3np3/2B5/5N2/PPPP1PPP/RNBQK2R w KQkq - 0 1",
+    "4Br1k/pp3p1p/8/2Qp2np/5P2/2P5/PP6/3q3K w - - 0 1",
+    "r2qk2r/ppp2ppp/2n1bn2/2bpp3/3PP3/2NB1N2/PPP2PPP/R1BQK2R w KQkq - 0 1",
+];
+
+/// Depth of each search, which is fixed so that every run searches the same tree
+const DEPTH: u32 = 5;
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let mut engine = Engine::default();
+
+    let mut group = c.benchmark_group("fixed depth search");
+    group
+        .sample_size(10)
+        .measurement_time(Duration::from_secs(60));
+
+    for fen in FENS {
+        group.bench_with_input(BenchmarkId::from_parameter(fen), fen, |b, fen| {
+            b.iter(|| {
+                // Start from a clean slate, so that earlier iterations don't speed up later ones
+                engine.clear_hash();
+                engine.set_position(Some(fen), std::iter::empty()).unwrap();
+                engine
+                    .search(UciSearchOptions {
+                        depth: Some(DEPTH),
+                        infinite: true,
+                        ..Default::default()
+                    })
+                    .unwrap()
+            });
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("killer moves");
+    group
+        .sample_size(10)
+        .measurement_time(Duration::from_secs(60));
+
+    for killers in [true, false] {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(killers),
+            &killers,
+            |b, &killers| {
+                engine.set_pruning(PruningConfig {
+                    killers,
+                    ..Default::default()
+                });
+                b.iter(|| {
+                    for fen in FENS {
+                        engine.clear_hash();
+                        engine.set_position(Some(fen), std::iter::empty()).unwrap();
+                        engine
+                            .search(UciSearchOptions {
+                                depth: Some(DEPTH),
+                                infinite: true,
+                                ..Default::default()
+                            })
+                            .unwrap();
+                    }
+                });
+            },
+        );
+    }
+    engine.set_pruning(PruningConfig::default());
+    group.finish();
+
+    let mut group = c.benchmark_group("quiescence search");
+    group.sample_size(100);
+
+    for fen in CAPTURE_FENS {
+        group.bench_with_input(BenchmarkId::from_parameter(fen), fen, |b, fen| {
+            b.iter(|| {
+                engine.set_position(Some(fen), std::iter::empty()).unwrap();
+                engine
+                    .quiescence_eval(&UciSearchOptions {
+                        infinite: true,
+                        ..Default::default()
+                    })
+                    .unwrap()
+            });
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("delta pruning");
+    group.sample_size(100);
+
+    for delta in [true, false] {
+        group.bench_with_input(BenchmarkId::from_parameter(delta), &delta, |b, &delta| {
+            engine.set_pruning(PruningConfig {
+                delta,
+                ..Default::default()
+            });
+            b.iter(|| {
+                for fen in CAPTURE_FENS {
+                    engine.set_position(Some(fen), std::iter::empty()).unwrap();
+                    engine
+                        .quiescence_eval(&UciSearchOptions {
+                            infinite: true,
+                            ..Default::default()
+                        })
+                        .unwrap();
+                }
+            });
+        });
+    }
+    engine.set_pruning(PruningConfig::default());
+    group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
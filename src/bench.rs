@@ -0,0 +1,78 @@
+//! Fixed-depth search over a suite of positions, run with `patch bench`
+//!
+//! The total node count is a signature of the search: any change to the search or the evaluation
+//! that isn't purely a speedup changes it, so it is used by testing frameworks (like OpenBench) to identify a build.
+//! The search runs on a single thread, as that is the only way for the node count to be deterministic.
+
+use std::time::Instant;
+
+use uci_parser::UciSearchOptions;
+
+use crate::engine::{Engine, EngineConfig, zobrist::HashScheme};
+
+/// Default depth that each position is searched to
+pub const DEFAULT_DEPTH: u32 = 6;
+
+/// Positions searched by [`run`], covering openings, middlegames, and endgames
+const FENS: [&str; 8] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "r1bq1rk1/pppp1ppp/2n2n2/2b1p3/2B1P3/2NP1N2/PPP2PPP/R1BQ1RK1 b - - 0 6",
+    "r1b2rk1/pp1n1p1p/1bp3qp/3p4/4p3/1QP2NNP/PP2BPPK/R4R2 w - - 0 1",
+    "4rr1k/pp3p1p/1b2n2p/3p1q2/1Q6/2P1pPPP/PP2B2K/2R2R2 b - - 0 1",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    "6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1",
+    "8/8/5k2/8/3B4/8/2K1P3/8 w - - 0 50",
+];
+
+/// Searches every position in the suite to `depth` with a fresh single-threaded engine,
+/// then prints the total nodes and nodes per second
+pub fn run(depth: u32) -> anyhow::Result<()> {
+    let start = Instant::now();
+    let nodes = count_nodes(depth)?;
+
+    let elapsed_ms = start.elapsed().as_millis();
+    println!(
+        "{} nodes {} nps",
+        nodes,
+        nodes as u128 * 1000 / elapsed_ms.max(1)
+    );
+
+    Ok(())
+}
+
+/// Total nodes searched over the suite to `depth`, with a fresh single-threaded engine for each position
+pub fn count_nodes(depth: u32) -> anyhow::Result<u64> {
+    let mut nodes = 0;
+
+    for fen in FENS {
+        let mut engine = Engine::with_config(EngineConfig {
+            threads: 1,
+            // The `chess` crate's keys may change between versions, which would change the node count
+            hash_scheme: HashScheme::Zobrist,
+            ..Default::default()
+        });
+        engine.set_position(Some(fen), std::iter::empty())?;
+
+        let result = engine.analyze(UciSearchOptions {
+            depth: Some(depth),
+            infinite: true,
+            ..Default::default()
+        })?;
+        nodes += result.nodes;
+    }
+
+    Ok(nodes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn node_count_is_deterministic() {
+        let first = count_nodes(3).unwrap();
+        assert!(first > 0);
+        assert_eq!(count_nodes(3).unwrap(), first);
+    }
+}
@@ -0,0 +1,186 @@
+//! Chess960 (Fischer Random Chess) castling, translated at the UCI boundary
+//!
+//! The `chess` crate only models standard castling: the king starts on the e-file, the rooks on the a- and h-files,
+//! and castling moves the king two squares (e.g. `e1g1`).
+//! With `UCI_Chess960` enabled, UCI writes castling as the king capturing its own rook instead (e.g. `e1h1`),
+//! and FENs name the castling rooks by their files (Shredder-FEN, e.g. `HAha`).
+//! Positions and moves are translated to the standard convention on the way in, and back on the way out.
+//!
+//! This covers every Chess960 position whose castling king and rooks stand on the standard squares
+//! (including the standard starting position, #518), and any position without castling rights.
+//! Other castling setups can't be represented by the board, so positions with them are rejected.
+
+use std::str::FromStr;
+
+use anyhow::{bail, ensure};
+use chess::{Board, CastleRights, ChessMove, Color, File, Piece, Square};
+
+/// Converts the castling field of a Shredder-FEN or X-FEN `fen` to the standard `KQkq` notation
+///
+/// Fails if the castling rights need the king or rooks anywhere other than their standard squares.
+pub fn standard_fen(fen: &str) -> anyhow::Result<String> {
+    let mut fields: Vec<&str> = fen.split_whitespace().collect();
+    let Some(castling) = fields.get(2) else {
+        // Malformed FENs are left for the board to report
+        return Ok(fen.to_string());
+    };
+
+    let rights = castling
+        .chars()
+        .map(|right| match right {
+            'H' => Ok('K'),
+            'A' => Ok('Q'),
+            'h' => Ok('k'),
+            'a' => Ok('q'),
+            'K' | 'Q' | 'k' | 'q' | '-' => Ok(right),
+            _ => bail!(
+                "Castling with a rook on the {}-file isn't supported: {}",
+                right.to_ascii_lowercase(),
+                fen
+            ),
+        })
+        .collect::<anyhow::Result<String>>()?;
+    fields[2] = &rights;
+    let standard = fields.join(" ");
+
+    let board = Board::from_str(&standard).map_err(anyhow::Error::msg)?;
+    for color in [Color::White, Color::Black] {
+        let rights = board.castle_rights(color);
+        if rights == CastleRights::NoRights {
+            continue;
+        }
+
+        let rank = color.to_my_backrank();
+        ensure!(
+            board.king_square(color) == Square::make_square(rank, File::E),
+            "Castling with the king off the e-file isn't supported: {}",
+            fen
+        );
+        for (has_right, file) in [
+            (rights.has_kingside(), File::H),
+            (rights.has_queenside(), File::A),
+        ] {
+            let rook = Square::make_square(rank, file);
+            ensure!(
+                !has_right
+                    || (board.piece_on(rook) == Some(Piece::Rook)
+                        && board.color_on(rook) == Some(color)),
+                "Castling rights without a rook on {}: {}",
+                rook,
+                fen
+            );
+        }
+    }
+
+    Ok(standard)
+}
+
+/// Converts a castling move on `board` from the Chess960 convention (the king capturing its own rook)
+/// to the standard one (the king moving two squares)
+///
+/// Every other move is returned unchanged.
+pub fn move_from_uci(board: &Board, mv: ChessMove) -> ChessMove {
+    let (source, dest) = (mv.get_source(), mv.get_dest());
+    let us = board.side_to_move();
+    let castles = board.piece_on(source) == Some(Piece::King)
+        && board.piece_on(dest) == Some(Piece::Rook)
+        && board.color_on(dest) == Some(us);
+    if !castles {
+        return mv;
+    }
+
+    let file = if dest.get_file().to_index() > source.get_file().to_index() {
+        File::G
+    } else {
+        File::C
+    };
+    ChessMove::new(source, Square::make_square(source.get_rank(), file), None)
+}
+
+/// Converts a castling move on `board` from the standard convention to the Chess960 one (see [`move_from_uci`])
+///
+/// Every other move is returned unchanged.
+pub fn move_to_uci(board: &Board, mv: ChessMove) -> ChessMove {
+    let (source, dest) = (mv.get_source(), mv.get_dest());
+    let (from, to) = (source.get_file().to_index(), dest.get_file().to_index());
+    if board.piece_on(source) != Some(Piece::King) || from.abs_diff(to) != 2 {
+        return mv;
+    }
+
+    let file = if to > from { File::H } else { File::A };
+    ChessMove::new(source, Square::make_square(source.get_rank(), file), None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::Engine;
+
+    /// Chess960 position #518, which is the standard starting position, in Shredder-FEN
+    const START_518: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w HAha - 0 1";
+
+    /// Both sides can castle either way, with nothing in between
+    const CASTLING: &str = "r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R w HAha - 0 1";
+
+    #[test]
+    fn shredder_fen_is_translated() {
+        assert_eq!(
+            standard_fen(START_518).unwrap(),
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"
+        );
+        // X-FEN already uses the standard letters
+        assert_eq!(
+            standard_fen(&START_518.replace("HAha", "KQkq")).unwrap(),
+            standard_fen(START_518).unwrap()
+        );
+    }
+
+    #[test]
+    fn unsupported_castling_is_rejected() {
+        // Chess960 position #0, where the king starts on the g-file, and a rook on the f-file
+        let fen = "bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w HFhf - 0 1";
+        assert!(standard_fen(fen).is_err());
+    }
+
+    #[test]
+    fn castling_moves_are_translated() {
+        let board = Board::from_str(&standard_fen(CASTLING).unwrap()).unwrap();
+        let legal: Vec<ChessMove> = chess::MoveGen::new_legal(&board)
+            .map(|mv| move_to_uci(&board, mv))
+            .collect();
+
+        for (uci, standard) in [("e1h1", "e1g1"), ("e1a1", "e1c1")] {
+            let uci = ChessMove::from_str(uci).unwrap();
+            assert!(legal.contains(&uci), "{} is missing from {:?}", uci, legal);
+            assert_eq!(
+                move_from_uci(&board, uci),
+                ChessMove::from_str(standard).unwrap()
+            );
+        }
+        // The king moving next to its rook isn't castling
+        let step = ChessMove::from_str("e1f1").unwrap();
+        assert_eq!(move_from_uci(&board, step), step);
+        assert_eq!(move_to_uci(&board, step), step);
+    }
+
+    #[test]
+    fn castling_is_applied() {
+        let mut engine = Engine::default();
+        engine.set_chess960(true);
+        let moves = ["e1h1", "e8a8"].map(|mv| ChessMove::from_str(mv).unwrap());
+        engine
+            .set_position(Some(CASTLING), moves.into_iter())
+            .unwrap();
+
+        let board = engine.board();
+        for (square, piece) in [
+            ("g1", Piece::King),
+            ("f1", Piece::Rook),
+            ("c8", Piece::King),
+            ("d8", Piece::Rook),
+        ] {
+            let square = Square::from_str(square).unwrap();
+            assert_eq!(board.piece_on(square), Some(piece), "on {}", square);
+        }
+    }
+}
@@ -1,97 +1,616 @@
-use chess::{BitBoard, Board, Color, EMPTY, Piece};
-use tables::{
-    ENDGAME_BISHOP_VALUE, ENDGAME_KING_VALUE, ENDGAME_KNIGHT_VALUE, ENDGAME_PAWN_VALUE,
-    ENDGAME_QUEEN_VALUE, ENDGAME_ROOK_VALUE, MIDGAME_BISHOP_VALUE, MIDGAME_KING_VALUE,
-    MIDGAME_KNIGHT_VALUE, MIDGAME_PAWN_VALUE, MIDGAME_QUEEN_VALUE, MIDGAME_ROOK_VALUE,
+use chess::{
+    ALL_PIECES, BitBoard, Board, Color, EMPTY, File, NUM_PIECES, Piece, Square, get_adjacent_files,
+    get_bishop_moves, get_file, get_king_moves, get_knight_moves, get_rank, get_rook_moves,
 };
 
 use crate::score::Score;
 
-/// Evaluation heuristic based on material and piece positions
-pub fn eval_heuristic(board: &Board) -> Score {
-    let score = piece_table_eval(board);
+/// All the light squares on the board (a1 is a dark square)
+const LIGHT_SQUARES: BitBoard = BitBoard(0x55AA_55AA_55AA_55AA);
 
-    Score::cp(score)
+/// Fraction (numerator, denominator) that evaluations are scaled by when the only minor pieces are opposite-colored bishops
+///
+/// These endgames are very drawish, even when one side is up a pawn or two
+const OPPOSITE_BISHOPS_SCALE: (i32, i32) = (1, 2);
+
+/// Fractions (numerator, denominator) that evaluations are scaled by when the side that is ahead has no pawns, or a single pawn
+///
+/// Without pawns to promote, a small material edge is often not enough to win (e.g. a rook and minor against a rook),
+/// so these positions are pulled towards a draw. This only applies up to [`FEW_PAWNS_MAX_EDGE`].
+const FEW_PAWNS_SCALE: [(i32, i32); 2] = [(1, 2), (3, 4)];
+
+/// Largest edge in piece material (not counting pawns) that [`FEW_PAWNS_SCALE`] applies to: a minor piece
+///
+/// Anything more (e.g. a lone rook or queen against a bare king) wins without pawns, so it isn't scaled
+const FEW_PAWNS_MAX_EDGE: i32 = piece_value(Piece::Bishop) as i32;
+
+/// Midgame and endgame bonus for a rook on the same file as, and behind, one of our passed pawns
+const ROOK_BEHIND_PASSER: (i32, i32) = (5, 20);
+
+/// Midgame and endgame penalty for a rook on the same file as, and in front of, one of the opponent's passed pawns
+const ROOK_IN_FRONT_OF_ENEMY_PASSER: (i32, i32) = (5, 15);
+
+/// Midgame and endgame penalty for a bishop trapped by an enemy pawn in the corner (see [`TRAPPED_BISHOPS`])
+const TRAPPED_BISHOP: (i32, i32) = (150, 100);
+
+/// Midgame and endgame penalty for a knight trapped by enemy pawns in the corner (see [`TRAPPED_KNIGHTS`])
+const TRAPPED_KNIGHT: (i32, i32) = (100, 75);
+
+/// Squares where a bishop is trapped, paired with the square of the enemy pawn that traps it, indexed by the bishop's color
+///
+/// E.g. a white bishop on a7 is trapped by a black pawn on b6, and will be lost to a pawn attack
+const TRAPPED_BISHOPS: [[(Square, Square); 4]; 2] = [
+    [
+        (Square::A7, Square::B6),
+        (Square::B8, Square::C7),
+        (Square::H7, Square::G6),
+        (Square::G8, Square::F7),
+    ],
+    [
+        (Square::A2, Square::B3),
+        (Square::B1, Square::C2),
+        (Square::H2, Square::G3),
+        (Square::G1, Square::F2),
+    ],
+];
+
+/// Corners where a knight is trapped, paired with the squares of the enemy pawns that trap it there, indexed by the knight's color
+///
+/// E.g. a white knight on a8 has no safe squares if a black pawn is on a7 or c7.
+/// Both pawns trap it no worse than either one, so each corner is only penalized once.
+const TRAPPED_KNIGHTS: [[(Square, [Square; 2]); 2]; 2] = [
+    [
+        (Square::A8, [Square::A7, Square::C7]),
+        (Square::H8, [Square::H7, Square::F7]),
+    ],
+    [
+        (Square::A1, [Square::A2, Square::C2]),
+        (Square::H1, [Square::H2, Square::F2]),
+    ],
+];
+
+/// Pieces that are rewarded for their mobility, in the order of [`MOBILITY`]
+const MOBILITY_PIECES: [Piece; 4] = [Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen];
+
+/// (midgame, endgame) bonus per square that each piece can move to, for knights, bishops, rooks, and queens
+///
+/// Rooks and queens have lots of moves anyway, so each one counts for less, especially in the midgame
+const MOBILITY: [(i32, i32); 4] = [(4, 4), (5, 5), (2, 4), (1, 2)];
+
+/// Midgame and endgame penalty for each file next to (or on) the king without a shield pawn in front of it
+///
+/// Shields only matter while there are pieces around to attack the king, so this fades out in the endgame
+const MISSING_SHIELD_PAWN: (i32, i32) = (20, 0);
+
+/// Midgame and endgame penalty for each enemy piece attacking the squares around the king
+const KING_ZONE_ATTACKER: (i32, i32) = (15, 3);
+
+/// Midgame and endgame bonus for a passed pawn, indexed by its rank from its own side of the board
+///
+/// Passed pawns get more dangerous the closer they are to promoting, and the fewer pieces are left to stop them
+const PASSED_PAWN: [(i32, i32); 8] = [
+    (0, 0),
+    (5, 10),
+    (5, 15),
+    (10, 25),
+    (20, 45),
+    (35, 75),
+    (60, 120),
+    (0, 0),
+];
+
+/// Midgame and endgame penalty for each pawn beyond the first on a file
+const DOUBLED_PAWN: (i32, i32) = (10, 25);
+
+/// Midgame and endgame penalty for each pawn with no friendly pawns on the adjacent files
+const ISOLATED_PAWN: (i32, i32) = (10, 15);
+
+/// Midgame and endgame bonus for having two (or more) bishops
+///
+/// The pair covers squares of both colors, which matters most in open endgames
+const BISHOP_PAIR: (i32, i32) = (30, 50);
+
+/// Weights of every evaluation term, so that they can be tuned without recompiling
+///
+/// All (midgame, endgame) pairs are tapered by the game phase, just like the built-in values.
+/// [`EvalParams::DEFAULT`] holds the values the engine ships with: the [PeSTO](https://www.chessprogramming.org/PeSTO%27s_Evaluation_Function)
+/// piece tables, and the constants documented on each term.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EvalParams {
+    /// Centipawn value of each piece, indexed by [`Piece::to_index`]
+    pub material: [(i16, i16); NUM_PIECES],
+    /// Positional value of each piece on each square, from white's perspective (a1 first), indexed by [`Piece::to_index`]
+    ///
+    /// Black's pieces use the same tables, mirrored vertically
+    pub positions: [([i16; 64], [i16; 64]); NUM_PIECES],
+    /// See [`ROOK_BEHIND_PASSER`]
+    pub rook_behind_passer: (i32, i32),
+    /// See [`ROOK_IN_FRONT_OF_ENEMY_PASSER`]
+    pub rook_in_front_of_enemy_passer: (i32, i32),
+    /// See [`TRAPPED_BISHOP`]
+    pub trapped_bishop: (i32, i32),
+    /// See [`TRAPPED_KNIGHT`]
+    pub trapped_knight: (i32, i32),
+    /// See [`MOBILITY`]
+    pub mobility: [(i32, i32); 4],
+    /// See [`MISSING_SHIELD_PAWN`]
+    pub missing_shield_pawn: (i32, i32),
+    /// See [`KING_ZONE_ATTACKER`]
+    pub king_zone_attacker: (i32, i32),
+    /// See [`PASSED_PAWN`]
+    pub passed_pawn: [(i32, i32); 8],
+    /// See [`DOUBLED_PAWN`]
+    pub doubled_pawn: (i32, i32),
+    /// See [`ISOLATED_PAWN`]
+    pub isolated_pawn: (i32, i32),
+    /// See [`BISHOP_PAIR`]
+    pub bishop_pair: (i32, i32),
+    /// See [`KING_ENDGAME_PHASE`]
+    pub king_endgame_phase: i32,
+}
+
+impl EvalParams {
+    /// The weights the engine ships with
+    pub const DEFAULT: Self = Self {
+        material: tables::MATERIAL_VALUES,
+        positions: tables::POSITION_VALUES,
+        rook_behind_passer: ROOK_BEHIND_PASSER,
+        rook_in_front_of_enemy_passer: ROOK_IN_FRONT_OF_ENEMY_PASSER,
+        trapped_bishop: TRAPPED_BISHOP,
+        trapped_knight: TRAPPED_KNIGHT,
+        mobility: MOBILITY,
+        missing_shield_pawn: MISSING_SHIELD_PAWN,
+        king_zone_attacker: KING_ZONE_ATTACKER,
+        passed_pawn: PASSED_PAWN,
+        doubled_pawn: DOUBLED_PAWN,
+        isolated_pawn: ISOLATED_PAWN,
+        bishop_pair: BISHOP_PAIR,
+        king_endgame_phase: KING_ENDGAME_PHASE,
+    };
+
+    /// Weights that only count material, with every piece table and term zeroed
+    ///
+    /// This is a baseline for measuring what the positional weights are worth (see `patch compare-eval`)
+    pub const MATERIAL_ONLY: Self = Self {
+        material: tables::MATERIAL_VALUES,
+        positions: [([0; 64], [0; 64]); NUM_PIECES],
+        rook_behind_passer: (0, 0),
+        rook_in_front_of_enemy_passer: (0, 0),
+        trapped_bishop: (0, 0),
+        trapped_knight: (0, 0),
+        mobility: [(0, 0); 4],
+        missing_shield_pawn: (0, 0),
+        king_zone_attacker: (0, 0),
+        passed_pawn: [(0, 0); 8],
+        doubled_pawn: (0, 0),
+        isolated_pawn: (0, 0),
+        bishop_pair: (0, 0),
+        king_endgame_phase: KING_ENDGAME_PHASE,
+    };
 }
 
-/// Scores the provided board using piece [`tables`]
+impl Default for EvalParams {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Evaluation terms on top of the piece tables, each scored as (midgame, endgame) from the perspective of the given color
+const TERMS: [fn(&Board, Color, &EvalParams) -> (i32, i32); 7] = [
+    rook_passer_eval,
+    trapped_pieces_eval,
+    mobility_eval,
+    king_safety_eval,
+    passed_pawn_eval,
+    pawn_structure_eval,
+    bishop_pair_eval,
+];
+
+/// Evaluation heuristic based on material and piece positions, weighted by `params`
+pub fn eval_heuristic(board: &Board, params: &EvalParams) -> Score {
+    let phase = game_phase(board);
+
+    let (mg, eg) = TERMS.iter().fold((0, 0), |(mg, eg), term| {
+        let (term_mg, term_eg) = relative(board, |color| term(board, color, params));
+        (mg + term_mg, eg + term_eg)
+    });
+    let mut score = piece_table_eval(board, params) + taper(mg, eg, phase);
+
+    if opposite_colored_bishops(board) {
+        score = score * OPPOSITE_BISHOPS_SCALE.0 / OPPOSITE_BISHOPS_SCALE.1;
+    }
+
+    let leader = if score >= 0 {
+        board.side_to_move()
+    } else {
+        !board.side_to_move()
+    };
+    let leader_pawns = (*board.pieces(Piece::Pawn) & *board.color_combined(leader)).popcnt();
+    let edge = piece_material(board, leader) - piece_material(board, !leader);
+    if let Some((num, den)) = FEW_PAWNS_SCALE
+        .get(leader_pawns as usize)
+        .filter(|_| edge <= FEW_PAWNS_MAX_EDGE)
+    {
+        score = score * num / den;
+    }
+
+    // Weights far outside the defaults (e.g. while tuning) can push the score out of range, so it saturates instead.
+    // The range is symmetric, so that the score can always be negated.
+    let score = score.clamp(-(i16::MAX as i32), i16::MAX as i32);
+    Score::cp(i16::try_from(score).unwrap_or_default())
+}
+
+/// Material value of `piece` in centipawns
+///
+/// This is the midgame value, for uses that only care about how pieces compare to each other (like move ordering)
+pub const fn piece_value(piece: Piece) -> i16 {
+    tables::midgame_material_values(piece)
+}
+
+/// Total [`piece_value`] of `color`'s knights, bishops, rooks, and queens
+fn piece_material(board: &Board, color: Color) -> i32 {
+    [Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen]
+        .into_iter()
+        .map(|piece| {
+            let count = (*board.pieces(piece) & *board.color_combined(color)).popcnt();
+            count as i32 * piece_value(piece) as i32
+        })
+        .sum()
+}
+
+/// Interpolates between a midgame and endgame score, based on the game `phase`
+fn taper(mg: i32, eg: i32, phase: i32) -> i32 {
+    (mg * phase + eg * (MAX_PHASE - phase)) / MAX_PHASE
+}
+
+/// Evaluates a (midgame, endgame) term for both players,
+/// returning the difference from the perspective of the player that is up to move
+fn relative(board: &Board, term: impl Fn(Color) -> (i32, i32)) -> (i32, i32) {
+    let (our_mg, our_eg) = term(board.side_to_move());
+    let (their_mg, their_eg) = term(!board.side_to_move());
+
+    (our_mg - their_mg, our_eg - their_eg)
+}
+
+/// All the squares on ranks strictly ahead of `square`, from the perspective of `color`
+fn ranks_ahead(square: Square, color: Color) -> BitBoard {
+    let rank = square.get_rank().to_index() as u32;
+
+    match color {
+        Color::White => BitBoard(u64::MAX.checked_shl(8 * (rank + 1)).unwrap_or(0)),
+        Color::Black => BitBoard((1u64 << (8 * rank)) - 1),
+    }
+}
+
+/// All of `color`'s passed pawns
+///
+/// A pawn is passed if there are no enemy pawns ahead of it, on its own file or the adjacent files
+pub fn passed_pawns(board: &Board, color: Color) -> BitBoard {
+    let pawns = *board.pieces(Piece::Pawn);
+    let ours = pawns & *board.color_combined(color);
+    let theirs = pawns & *board.color_combined(!color);
+
+    ours.filter(|square| {
+        let file = square.get_file();
+        let span = (get_file(file) | get_adjacent_files(file)) & ranks_ahead(*square, color);
+
+        span & theirs == EMPTY
+    })
+    .fold(EMPTY, |passed, square| {
+        passed | BitBoard::from_square(square)
+    })
+}
+
+/// Rewards `color`'s passed pawns, more so the further they are advanced (see [`PASSED_PAWN`])
+fn passed_pawn_eval(board: &Board, color: Color, params: &EvalParams) -> (i32, i32) {
+    passed_pawns(board, color).fold((0, 0), |(mg, eg), pawn| {
+        let rank = match color {
+            Color::White => pawn.get_rank().to_index(),
+            Color::Black => 7 - pawn.get_rank().to_index(),
+        };
+        let (bonus_mg, bonus_eg) = params.passed_pawn[rank];
+
+        (mg + bonus_mg, eg + bonus_eg)
+    })
+}
+
+/// Number of `color`'s pawns that share their file with another of their pawns, not counting the first one on each file
+pub fn doubled_pawns(board: &Board, color: Color) -> u32 {
+    let ours = *board.pieces(Piece::Pawn) & *board.color_combined(color);
+
+    (0..8)
+        .map(|file| {
+            (ours & get_file(File::from_index(file)))
+                .popcnt()
+                .saturating_sub(1)
+        })
+        .sum()
+}
+
+/// All of `color`'s isolated pawns
+///
+/// A pawn is isolated if there are no friendly pawns on the adjacent files, so no pawn can ever defend it
+pub fn isolated_pawns(board: &Board, color: Color) -> BitBoard {
+    let ours = *board.pieces(Piece::Pawn) & *board.color_combined(color);
+
+    ours.filter(|square| get_adjacent_files(square.get_file()) & ours == EMPTY)
+        .fold(EMPTY, |isolated, square| {
+            isolated | BitBoard::from_square(square)
+        })
+}
+
+/// Penalizes weaknesses in `color`'s pawn structure: doubled and isolated pawns
+fn pawn_structure_eval(board: &Board, color: Color, params: &EvalParams) -> (i32, i32) {
+    let doubled = doubled_pawns(board, color) as i32;
+    let isolated = isolated_pawns(board, color).popcnt() as i32;
+
+    (
+        -doubled * params.doubled_pawn.0 - isolated * params.isolated_pawn.0,
+        -doubled * params.doubled_pawn.1 - isolated * params.isolated_pawn.1,
+    )
+}
+
+/// Rewards `color` for holding the bishop pair (see [`BISHOP_PAIR`])
+fn bishop_pair_eval(board: &Board, color: Color, params: &EvalParams) -> (i32, i32) {
+    let bishops = *board.pieces(Piece::Bishop) & *board.color_combined(color);
+
+    if bishops.popcnt() >= 2 {
+        params.bishop_pair
+    } else {
+        (0, 0)
+    }
+}
+
+/// Evaluates `color`'s rooks relative to the passed pawns on the board (the Tarrasch rule)
+///
+/// Rooks belong behind passed pawns: behind our own to support their advance,
+/// and not in front of the opponent's, where they are tied down to passive blockading.
+fn rook_passer_eval(board: &Board, color: Color, params: &EvalParams) -> (i32, i32) {
+    let rooks = *board.pieces(Piece::Rook) & *board.color_combined(color);
+    if rooks == EMPTY {
+        return (0, 0);
+    }
+
+    // "Behind" our pawns and "in front" of their pawns are both towards our side of the board
+    let behind_ours: u32 = passed_pawns(board, color)
+        .map(|pawn| (rooks & get_file(pawn.get_file()) & ranks_ahead(pawn, !color)).popcnt())
+        .sum();
+    let in_front_of_theirs: u32 = passed_pawns(board, !color)
+        .map(|pawn| (rooks & get_file(pawn.get_file()) & ranks_ahead(pawn, !color)).popcnt())
+        .sum();
+
+    let (behind, in_front) = (behind_ours as i32, in_front_of_theirs as i32);
+    (
+        behind * params.rook_behind_passer.0 - in_front * params.rook_in_front_of_enemy_passer.0,
+        behind * params.rook_behind_passer.1 - in_front * params.rook_in_front_of_enemy_passer.1,
+    )
+}
+
+/// Rewards `color`'s pieces for the number of squares they can move to (see [`MOBILITY`])
+///
+/// Moves are counted pseudo-legally (ignoring pins and checks), as that is much cheaper and almost always the same
+fn mobility_eval(board: &Board, color: Color, params: &EvalParams) -> (i32, i32) {
+    let ours = *board.color_combined(color);
+    let blockers = *board.combined();
+
+    MOBILITY_PIECES.iter().zip(params.mobility).fold(
+        (0, 0),
+        |(mg, eg), (piece, (piece_mg, piece_eg))| {
+            let moves: u32 = (*board.pieces(*piece) & ours)
+                .map(|square| (piece_attacks(*piece, square, blockers) & !ours).popcnt())
+                .sum();
+
+            (mg + moves as i32 * piece_mg, eg + moves as i32 * piece_eg)
+        },
+    )
+}
+
+/// Squares attacked by a knight, bishop, rook, or queen on `square`, where sliding pieces are stopped by `blockers`
+///
+/// Pawns and kings aren't covered, as they aren't counted by any of the terms using this
+fn piece_attacks(piece: Piece, square: Square, blockers: BitBoard) -> BitBoard {
+    match piece {
+        Piece::Knight => get_knight_moves(square),
+        Piece::Bishop => get_bishop_moves(square, blockers),
+        Piece::Rook => get_rook_moves(square, blockers),
+        _ => get_bishop_moves(square, blockers) | get_rook_moves(square, blockers),
+    }
+}
+
+/// Evaluates the safety of `color`'s king, from its pawn shield and the enemy pieces bearing down on it
+///
+/// The shield is the two ranks in front of the king, on its own file and the adjacent ones,
+/// and only counts while the king is still on its first two ranks (i.e. castled, or hasn't moved).
+fn king_safety_eval(board: &Board, color: Color, params: &EvalParams) -> (i32, i32) {
+    let king = board.king_square(color);
+    let blockers = *board.combined();
+
+    let home = match color {
+        Color::White => king.get_rank().to_index() <= 1,
+        Color::Black => king.get_rank().to_index() >= 6,
+    };
+    let missing_shield = if home {
+        let our_pawns = *board.pieces(Piece::Pawn) & *board.color_combined(color);
+        let one_ahead = king.forward(color);
+        let two_ahead = one_ahead.and_then(|square| square.forward(color));
+        let shield_ranks = [one_ahead, two_ahead]
+            .into_iter()
+            .flatten()
+            .fold(EMPTY, |ranks, square| ranks | get_rank(square.get_rank()));
+
+        let file = king.get_file().to_index();
+        (file.saturating_sub(1)..=(file + 1).min(7))
+            .filter(|file| get_file(File::from_index(*file)) & shield_ranks & our_pawns == EMPTY)
+            .count() as i32
+    } else {
+        0
+    };
+
+    let zone = get_king_moves(king) | BitBoard::from_square(king);
+    let theirs = *board.color_combined(!color);
+    let attackers = [Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen]
+        .into_iter()
+        .map(|piece| {
+            (*board.pieces(piece) & theirs)
+                .filter(|square| piece_attacks(piece, *square, blockers) & zone != EMPTY)
+                .count() as i32
+        })
+        .sum::<i32>();
+
+    (
+        -missing_shield * params.missing_shield_pawn.0 - attackers * params.king_zone_attacker.0,
+        -missing_shield * params.missing_shield_pawn.1 - attackers * params.king_zone_attacker.1,
+    )
+}
+
+/// Penalizes `color`'s bishops and knights that are trapped in well-known patterns
+///
+/// A full mobility check would be more general, but these patterns cover the most common cases cheaply
+fn trapped_pieces_eval(board: &Board, color: Color, params: &EvalParams) -> (i32, i32) {
+    let ours = *board.color_combined(color);
+    let their_pawns = *board.pieces(Piece::Pawn) & *board.color_combined(!color);
+
+    let on = |pieces: BitBoard, square: Square| pieces & BitBoard::from_square(square) != EMPTY;
+
+    let our_bishops = *board.pieces(Piece::Bishop) & ours;
+    let bishops = TRAPPED_BISHOPS[color.to_index()]
+        .iter()
+        .filter(|(bishop, pawn)| on(our_bishops, *bishop) && on(their_pawns, *pawn))
+        .count() as i32;
+    let our_knights = *board.pieces(Piece::Knight) & ours;
+    let knights = TRAPPED_KNIGHTS[color.to_index()]
+        .iter()
+        .filter(|(knight, pawns)| {
+            on(our_knights, *knight) && pawns.iter().any(|pawn| on(their_pawns, *pawn))
+        })
+        .count() as i32;
+
+    (
+        -bishops * params.trapped_bishop.0 - knights * params.trapped_knight.0,
+        -bishops * params.trapped_bishop.1 - knights * params.trapped_knight.1,
+    )
+}
+
+/// Whether neither side has enough material left to ever checkmate, so the game is a dead draw
+///
+/// This is the case with bare kings, a single minor piece, or any number of bishops that are all on the same colored squares
+pub fn is_insufficient_material(board: &Board) -> bool {
+    let heavy =
+        *board.pieces(Piece::Pawn) | *board.pieces(Piece::Rook) | *board.pieces(Piece::Queen);
+    if heavy != EMPTY {
+        return false;
+    }
+
+    let knights = *board.pieces(Piece::Knight);
+    let bishops = *board.pieces(Piece::Bishop);
+
+    (knights | bishops).popcnt() <= 1
+        || (knights == EMPTY
+            && (bishops & LIGHT_SQUARES == EMPTY || bishops & !LIGHT_SQUARES == EMPTY))
+}
+
+/// Whether the only minor pieces on the board are a single bishop for each side, on opposite colored squares
+fn opposite_colored_bishops(board: &Board) -> bool {
+    if *board.pieces(Piece::Knight) != EMPTY {
+        return false;
+    }
+
+    let bishops = *board.pieces(Piece::Bishop);
+    let white = bishops & *board.color_combined(Color::White);
+    let black = bishops & *board.color_combined(Color::Black);
+
+    white.popcnt() == 1
+        && black.popcnt() == 1
+        && ((white & LIGHT_SQUARES) == EMPTY) != ((black & LIGHT_SQUARES) == EMPTY)
+}
+
+/// Scores the provided board using the piece tables in `params` (see [`EvalParams::positions`])
 ///
 /// Always scored from the perspective of the player that is up to move
 /// Pieces are given values based both on their material value and their position on the board
-fn piece_table_eval(board: &Board) -> i16 {
-    let combined = board.combined();
+fn piece_table_eval(board: &Board, params: &EvalParams) -> i32 {
+    let phase = game_phase(board);
+    let inverse_phase = MAX_PHASE - phase;
+
+    // Kings are tapered on their own curve, see `king_table_eval`
+    let (mg_score, eg_score) = ALL_PIECES[..Piece::King.to_index()]
+        .iter()
+        .flat_map(|piece| (*board.pieces(*piece)).map(move |square| (*piece, square)))
+        .map(|(piece, square)| {
+            let (index, ours) =
+                color_helper(board, BitBoard::from_square(square), square.to_index());
+            let (material_mg, material_eg) = params.material[piece.to_index()];
+            let (position_mg, position_eg) = &params.positions[piece.to_index()];
+
+            (
+                (material_mg as i32 + position_mg[index] as i32) * ours as i32,
+                (material_eg as i32 + position_eg[index] as i32) * ours as i32,
+            )
+        })
+        .fold((0, 0), |(mg_acc, eg_acc), (mg_score, eg_score)| {
+            (mg_acc + mg_score, eg_acc + eg_score)
+        });
 
-    let pawns = board.pieces(Piece::Pawn);
-    let knights = board.pieces(Piece::Knight);
-    let bishops = board.pieces(Piece::Bishop);
-    let rooks = board.pieces(Piece::Rook);
-    let queens = board.pieces(Piece::Queen);
-    let kings = board.pieces(Piece::King);
+    let pieces = (mg_score * phase + eg_score * inverse_phase) / MAX_PHASE;
 
-    let phase = (knights.popcnt() + bishops.popcnt() + 2 * rooks.popcnt() + 4 * queens.popcnt())
-        .min(24) as i32; // Account for early promotion
+    pieces + king_table_eval(board, phase, params)
+}
 
-    let inverse_phase = 24 - phase;
+/// Default game phase at (and below) which the kings are evaluated purely with their endgame tables
+///
+/// Above this threshold, the king's midgame and endgame tables are blended linearly up to [`MAX_PHASE`].
+/// This gives the king a sharper transition than the other pieces: it stays safe while there is material to attack it,
+/// and centralizes as soon as the position simplifies.
+/// A threshold of zero blends the king tables just like every other piece.
+pub const KING_ENDGAME_PHASE: i32 = 6;
 
-    let (mg_score, eg_score) = (0..64)
+/// Game phase used to taper the king tables, with the kings fully in the endgame at `threshold` (see [`KING_ENDGAME_PHASE`])
+///
+/// The threshold is kept below [`MAX_PHASE`], so that the kings still reach their midgame tables in the opening
+fn king_phase(phase: i32, threshold: i32) -> i32 {
+    let threshold = threshold.clamp(0, MAX_PHASE - 1);
+    (phase - threshold).max(0) * MAX_PHASE / (MAX_PHASE - threshold)
+}
+
+/// Scores the kings' positions using their piece tables in `params`, tapered with [`king_phase`]
+///
+/// Always scored from the perspective of the player that is up to move
+fn king_table_eval(board: &Board, phase: i32, params: &EvalParams) -> i32 {
+    let (material_mg, material_eg) = params.material[Piece::King.to_index()];
+    let (position_mg, position_eg) = &params.positions[Piece::King.to_index()];
+    let phase = king_phase(phase, params.king_endgame_phase);
+
+    [board.side_to_move(), !board.side_to_move()]
         .into_iter()
-        .map(|i: usize| {
-            let square = BitBoard::new(1u64 << i);
-
-            if combined & square == EMPTY {
-                // No piece here, just return the identity
-                (0, 0)
-            } else if pawns & square != EMPTY {
-                // Found a pawn
-                let (index, ours) = color_helper(board, square, i);
-                (
-                    MIDGAME_PAWN_VALUE[index] * ours,
-                    ENDGAME_PAWN_VALUE[index] * ours,
-                )
-            } else if knights & square != EMPTY {
-                // Found a knight
-                let (index, ours) = color_helper(board, square, i);
-                (
-                    MIDGAME_KNIGHT_VALUE[index] * ours,
-                    ENDGAME_KNIGHT_VALUE[index] * ours,
-                )
-            } else if bishops & square != EMPTY {
-                // Found a bishop
-                let (index, ours) = color_helper(board, square, i);
-                (
-                    MIDGAME_BISHOP_VALUE[index] * ours,
-                    ENDGAME_BISHOP_VALUE[index] * ours,
-                )
-            } else if rooks & square != EMPTY {
-                // Found a rook
-                let (index, ours) = color_helper(board, square, i);
-                (
-                    MIDGAME_ROOK_VALUE[index] * ours,
-                    ENDGAME_ROOK_VALUE[index] * ours,
-                )
-            } else if queens & square != EMPTY {
-                // Found a queen
-                let (index, ours) = color_helper(board, square, i);
-                (
-                    MIDGAME_QUEEN_VALUE[index] * ours,
-                    ENDGAME_QUEEN_VALUE[index] * ours,
-                )
-            } else if kings & square != EMPTY {
-                // Found a king
-                let (index, ours) = color_helper(board, square, i);
-                (
-                    MIDGAME_KING_VALUE[index] * ours,
-                    ENDGAME_KING_VALUE[index] * ours,
-                )
-            } else {
-                unreachable!()
-            }
+        .zip([1, -1])
+        .map(|(color, mult)| {
+            let square = board.king_square(color).to_index();
+            let index = match color {
+                Color::White => square,
+                Color::Black => square ^ 56,
+            };
+
+            let mg = material_mg as i32 + position_mg[index] as i32;
+            let eg = material_eg as i32 + position_eg[index] as i32;
+            taper(mg, eg, phase) * mult
         })
-        .reduce(|(mg_acc, eg_acc), (mg_score, eg_score)| (mg_acc + mg_score, eg_acc + eg_score))
-        .unwrap();
+        .sum()
+}
+
+/// The game phase of the starting position (and of any position with at least as much material)
+pub const MAX_PHASE: i32 = 24;
+
+/// Game phase used to taper between midgame and endgame evaluations, based on the non-pawn material on the board
+///
+/// Ranges from [`MAX_PHASE`] (midgame) down to zero (bare kings and pawns)
+pub fn game_phase(board: &Board) -> i32 {
+    let knights = board.pieces(Piece::Knight);
+    let bishops = board.pieces(Piece::Bishop);
+    let rooks = board.pieces(Piece::Rook);
+    let queens = board.pieces(Piece::Queen);
 
-    // TODO: Benchmark these casts to try to make them faster. Maybe move everything to i32?
-    (((mg_score as i32) * phase + (eg_score as i32) * inverse_phase) / 24) as i16
+    ((knights.popcnt() + bishops.popcnt() + 2 * rooks.popcnt() + 4 * queens.popcnt()) as i32)
+        .min(MAX_PHASE) // Account for early promotion
 }
 
 /// Assumes a piece is present on `square` of `board`.
@@ -129,7 +648,7 @@ mod tables {
     /// Centipawn values for each piece during the midgame
     ///
     /// Actual values are interpolated between mid and endgame, based on how much material is on the board
-    const fn midgame_material_values(piece: Piece) -> i16 {
+    pub const fn midgame_material_values(piece: Piece) -> i16 {
         match piece {
             Piece::Pawn => 82,
             Piece::Knight => 337,
@@ -310,147 +829,316 @@ mod tables {
         -74, -35, -18, -18, -11,  15,   4, -17,
     ];
 
-    /// Piece table for a pawn in the midgame, combining its inherent material value and its positional value
-    pub const MIDGAME_PAWN_VALUE: [i16; 64] = {
-        let (mut table, mut i) = ([0; 64], 0);
+    /// Midgame and endgame material values of each piece, indexed by [`Piece::to_index`]
+    pub const MATERIAL_VALUES: [(i16, i16); NUM_PIECES] = {
+        let (mut values, mut i) = ([(0, 0); NUM_PIECES], 0);
 
-        while i < 64 {
-            table[i] = MIDGAME_PAWN_POSITION_VALUE[i] + midgame_material_values(Piece::Pawn);
+        while i < NUM_PIECES {
+            let piece = ALL_PIECES[i];
+            values[i] = (
+                midgame_material_values(piece),
+                endgame_material_values(piece),
+            );
             i += 1;
         }
 
-        table
+        values
     };
 
-    /// Piece table for a pawn in the endgame, combining its inherent material value and its positional value
-    pub const ENDGAME_PAWN_VALUE: [i16; 64] = {
-        let (mut table, mut i) = ([0; 64], 0);
+    /// Midgame and endgame positional values of each piece, indexed by [`Piece::to_index`]
+    pub const POSITION_VALUES: [([i16; 64], [i16; 64]); NUM_PIECES] = [
+        (MIDGAME_PAWN_POSITION_VALUE, ENDGAME_PAWN_POSITION_VALUE),
+        (MIDGAME_KNIGHT_POSITION_VALUE, ENDGAME_KNIGHT_POSITION_VALUE),
+        (MIDGAME_BISHOP_POSITION_VALUE, ENDGAME_BISHOP_POSITION_VALUE),
+        (MIDGAME_ROOK_POSITION_VALUE, ENDGAME_ROOK_POSITION_VALUE),
+        (MIDGAME_QUEEN_POSITION_VALUE, ENDGAME_QUEEN_POSITION_VALUE),
+        (MIDGAME_KING_POSITION_VALUE, ENDGAME_KING_POSITION_VALUE),
+    ];
+}
 
-        while i < 64 {
-            table[i] = ENDGAME_PAWN_POSITION_VALUE[i] + endgame_material_values(Piece::Pawn);
-            i += 1;
-        }
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
 
-        table
-    };
+    use super::*;
 
-    /// Piece table for a knight in the midgame, combining its inherent material value and its positional value
-    pub const MIDGAME_KNIGHT_VALUE: [i16; 64] = {
-        let (mut table, mut i) = ([0; 64], 0);
+    /// The default evaluation of `fen`
+    fn eval(fen: &str) -> Score {
+        eval_heuristic(&Board::from_str(fen).unwrap(), &EvalParams::DEFAULT)
+    }
 
-        while i < 64 {
-            table[i] = MIDGAME_KNIGHT_POSITION_VALUE[i] + midgame_material_values(Piece::Knight);
-            i += 1;
-        }
+    /// `term` for white on `fen`, with the default weights
+    fn white(term: fn(&Board, Color, &EvalParams) -> (i32, i32), fen: &str) -> (i32, i32) {
+        term(
+            &Board::from_str(fen).unwrap(),
+            Color::White,
+            &EvalParams::DEFAULT,
+        )
+    }
 
-        table
-    };
+    #[test]
+    fn opposite_bishops_are_scaled_down() {
+        // White is three pawns up in both, with the bishops on opposite colored squares in the first, and on the same in the second
+        let opposite = eval("4k3/8/8/3b4/8/8/PPP5/2B1K3 w - - 0 1");
+        let same = eval("4k3/8/8/4b3/8/8/PPP5/2B1K3 w - - 0 1");
+        assert!(
+            Score::cp(0) < opposite && opposite < same,
+            "{:?} vs {:?}",
+            opposite,
+            same
+        );
+    }
 
-    /// Piece table for a knight in the endgame, combining its inherent material value and its positional value
-    pub const ENDGAME_KNIGHT_VALUE: [i16; 64] = {
-        let (mut table, mut i) = ([0; 64], 0);
+    #[test]
+    fn rooks_belong_behind_passed_pawns() {
+        // Behind our passed pawn, and then in front of it
+        assert_eq!(
+            white(rook_passer_eval, "4k3/8/8/8/P7/8/8/R3K3 w - - 0 1"),
+            ROOK_BEHIND_PASSER
+        );
+        assert_eq!(
+            white(rook_passer_eval, "R3k3/8/8/8/P7/8/8/4K3 w - - 0 1"),
+            (0, 0)
+        );
+        // In front of their passed pawn, and then behind it
+        assert_eq!(
+            white(rook_passer_eval, "4k3/8/8/p7/8/8/8/R3K3 w - - 0 1"),
+            (
+                -ROOK_IN_FRONT_OF_ENEMY_PASSER.0,
+                -ROOK_IN_FRONT_OF_ENEMY_PASSER.1
+            )
+        );
+        assert_eq!(
+            white(rook_passer_eval, "R3k3/8/8/p7/8/8/8/4K3 w - - 0 1"),
+            (0, 0)
+        );
+    }
 
-        while i < 64 {
-            table[i] = ENDGAME_KNIGHT_POSITION_VALUE[i] + endgame_material_values(Piece::Knight);
-            i += 1;
+    #[test]
+    fn cornered_knights_are_trapped_once() {
+        let trapped = (-TRAPPED_KNIGHT.0, -TRAPPED_KNIGHT.1);
+        // Trapped by either pawn, or both
+        for fen in [
+            "N3k3/p7/8/8/8/8/8/4K3 w - - 0 1",
+            "N3k3/2p5/8/8/8/8/8/4K3 w - - 0 1",
+            "N3k3/p1p5/8/8/8/8/8/4K3 w - - 0 1",
+        ] {
+            assert_eq!(white(trapped_pieces_eval, fen), trapped, "{}", fen);
         }
-
-        table
-    };
-
-    /// Piece table for a bishop in the midgame, combining its inherent material value and its positional value
-    pub const MIDGAME_BISHOP_VALUE: [i16; 64] = {
-        let (mut table, mut i) = ([0; 64], 0);
-
-        while i < 64 {
-            table[i] = MIDGAME_BISHOP_POSITION_VALUE[i] + midgame_material_values(Piece::Bishop);
-            i += 1;
+        // The same for black's knights, in the opposite corner
+        assert_eq!(
+            trapped_pieces_eval(
+                &Board::from_str("4k3/8/8/8/8/8/7P/4K2n b - - 0 1").unwrap(),
+                Color::Black,
+                &EvalParams::DEFAULT
+            ),
+            trapped
+        );
+
+        // Free to leave the corner, or not in the corner at all
+        for fen in [
+            "N3k3/8/8/8/8/8/8/4K3 w - - 0 1",
+            "4k3/p1p5/8/8/8/8/8/N3K3 w - - 0 1",
+            "1N2k3/p1p5/8/8/8/8/8/4K3 w - - 0 1",
+        ] {
+            assert_eq!(white(trapped_pieces_eval, fen), (0, 0), "{}", fen);
         }
+    }
 
-        table
-    };
-
-    /// Piece table for a bishop in the endgame, combining its inherent material value and its positional value
-    pub const ENDGAME_BISHOP_VALUE: [i16; 64] = {
-        let (mut table, mut i) = ([0; 64], 0);
-
-        while i < 64 {
-            table[i] = ENDGAME_BISHOP_POSITION_VALUE[i] + endgame_material_values(Piece::Bishop);
-            i += 1;
-        }
+    #[test]
+    fn endgame_kings_centralize_below_the_threshold() {
+        // A rook and a bishop each (phase 6), with white's king in the center and black's on its back rank
+        let board = Board::from_str("4k3/4r3/3b4/8/3K4/8/8/3RB3 w - - 0 1").unwrap();
+        let phase = game_phase(&board);
+        let with_threshold = |king_endgame_phase| EvalParams {
+            king_endgame_phase,
+            ..EvalParams::DEFAULT
+        };
 
-        table
-    };
+        // At the threshold, the kings are scored by their endgame tables alone, which favor the center
+        let endgame = king_table_eval(&board, phase, &with_threshold(phase));
+        assert!(endgame > 0, "{}", endgame);
+        assert_eq!(
+            king_table_eval(&board, phase, &EvalParams::DEFAULT),
+            endgame
+        );
+        // Without a threshold, the midgame tables still pull the centralized king back
+        let blended = king_table_eval(&board, phase, &with_threshold(0));
+        assert!(blended < endgame, "{} >= {}", blended, endgame);
+        assert_eq!(king_phase(phase, phase), 0);
+    }
 
-    /// Piece table for a rook in the midgame, combining its inherent material value and its positional value
-    pub const MIDGAME_ROOK_VALUE: [i16; 64] = {
-        let (mut table, mut i) = ([0; 64], 0);
+    #[test]
+    fn pawnless_minor_edges_are_drawish() {
+        // A rook and bishop against a rook, without pawns and then with a few pawns each
+        let pawnless = eval("r3k3/8/8/8/8/8/8/R1B1K3 w - - 0 1");
+        let with_pawns = eval("r3k3/ppp5/8/8/8/8/PPP5/R1B1K3 w - - 0 1");
+        assert!(
+            Score::cp(0) < pawnless && pawnless < with_pawns,
+            "{:?} vs {:?}",
+            pawnless,
+            with_pawns
+        );
+    }
 
-        while i < 64 {
-            table[i] = MIDGAME_ROOK_POSITION_VALUE[i] + midgame_material_values(Piece::Rook);
-            i += 1;
+    #[test]
+    fn pawnless_wins_are_not_scaled() {
+        // A rook, and then a queen, against a bare king
+        for (fen, minimum) in [
+            ("4k3/8/8/8/8/8/8/R3K3 w - - 0 1", 400),
+            ("4k3/8/8/8/8/8/8/Q3K3 w - - 0 1", 800),
+        ] {
+            let score = eval(fen);
+            assert!(score >= Score::cp(minimum), "{:?} on {}", score, fen);
         }
+    }
 
-        table
-    };
-
-    /// Piece table for a rook in the endgame, combining its inherent material value and its positional value
-    pub const ENDGAME_ROOK_VALUE: [i16; 64] = {
-        let (mut table, mut i) = ([0; 64], 0);
-
-        while i < 64 {
-            table[i] = ENDGAME_ROOK_POSITION_VALUE[i] + endgame_material_values(Piece::Rook);
-            i += 1;
-        }
+    #[test]
+    fn centralized_knights_are_more_mobile() {
+        let knight_moves = |moves: i32| (moves * MOBILITY[0].0, moves * MOBILITY[0].1);
+        // In the center, then in the corner, then in the center next to one of our own pawns
+        assert_eq!(
+            white(mobility_eval, "4k3/8/8/8/3N4/8/8/4K3 w - - 0 1"),
+            knight_moves(8)
+        );
+        assert_eq!(
+            white(mobility_eval, "4k3/8/8/8/8/8/8/N3K3 w - - 0 1"),
+            knight_moves(2)
+        );
+        assert_eq!(
+            white(mobility_eval, "4k3/8/8/8/3N4/8/4P3/4K3 w - - 0 1"),
+            knight_moves(7)
+        );
+    }
 
-        table
-    };
+    #[test]
+    fn kings_want_a_shield_and_no_attackers() {
+        // Castled behind a full shield, then without the h-pawn, then with a knight hitting f2
+        assert_eq!(
+            white(king_safety_eval, "4k3/8/8/8/8/8/5PPP/6K1 w - - 0 1"),
+            (0, 0)
+        );
+        assert_eq!(
+            white(king_safety_eval, "4k3/8/8/8/8/8/5PP1/6K1 w - - 0 1"),
+            (-MISSING_SHIELD_PAWN.0, -MISSING_SHIELD_PAWN.1)
+        );
+        assert_eq!(
+            white(king_safety_eval, "4k3/8/8/8/4n3/8/5PPP/6K1 w - - 0 1"),
+            (-KING_ZONE_ATTACKER.0, -KING_ZONE_ATTACKER.1)
+        );
+        // A shield pawn that has moved up a rank still counts
+        assert_eq!(
+            white(king_safety_eval, "4k3/8/8/8/8/7P/5PP1/6K1 w - - 0 1"),
+            (0, 0)
+        );
+    }
 
-    /// Piece table for a queen in the midgame, combining its inherent material value and its positional value
-    pub const MIDGAME_QUEEN_VALUE: [i16; 64] = {
-        let (mut table, mut i) = ([0; 64], 0);
+    #[test]
+    fn passed_pawns_are_worth_more_as_they_advance() {
+        // Stopped by the pawn on the next file, then passed on the fifth rank, then passed on the sixth
+        assert_eq!(
+            white(passed_pawn_eval, "4k3/3p4/8/4P3/8/8/8/4K3 w - - 0 1"),
+            (0, 0)
+        );
+        assert_eq!(
+            white(passed_pawn_eval, "4k3/1p6/8/4P3/8/8/8/4K3 w - - 0 1"),
+            PASSED_PAWN[4]
+        );
+        assert_eq!(
+            white(passed_pawn_eval, "4k3/1p6/4P3/8/8/8/8/4K3 w - - 0 1"),
+            PASSED_PAWN[5]
+        );
+        // A pawn that is level with or behind an enemy pawn on the next file is still passed
+        assert_eq!(
+            white(passed_pawn_eval, "4k3/8/8/3pP3/8/8/8/4K3 w - - 0 1"),
+            PASSED_PAWN[4]
+        );
+    }
 
-        while i < 64 {
-            table[i] = MIDGAME_QUEEN_POSITION_VALUE[i] + midgame_material_values(Piece::Queen);
-            i += 1;
-        }
+    #[test]
+    fn doubled_and_isolated_pawns_are_penalized() {
+        // A healthy chain, then a doubled pawn, then two isolated pawns
+        assert_eq!(
+            white(pawn_structure_eval, "4k3/8/8/8/8/8/PPP5/4K3 w - - 0 1"),
+            (0, 0)
+        );
+        assert_eq!(
+            white(pawn_structure_eval, "4k3/8/8/8/8/P7/PP6/4K3 w - - 0 1"),
+            (-DOUBLED_PAWN.0, -DOUBLED_PAWN.1)
+        );
+        assert_eq!(
+            white(pawn_structure_eval, "4k3/8/8/8/8/8/P1P5/4K3 w - - 0 1"),
+            (-2 * ISOLATED_PAWN.0, -2 * ISOLATED_PAWN.1)
+        );
+    }
 
-        table
-    };
+    #[test]
+    fn only_the_pair_of_bishops_gets_a_bonus() {
+        assert_eq!(
+            white(bishop_pair_eval, "4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1"),
+            BISHOP_PAIR
+        );
+        assert_eq!(
+            white(bishop_pair_eval, "4k3/8/8/8/8/8/8/2B1KN2 w - - 0 1"),
+            (0, 0)
+        );
+        // Only our own bishops count towards the pair
+        assert_eq!(
+            white(bishop_pair_eval, "4kb2/8/8/8/8/8/8/2B1K3 w - - 0 1"),
+            (0, 0)
+        );
+    }
 
-    /// Piece table for a queen in the endgame, combining its inherent material value and its positional value
-    pub const ENDGAME_QUEEN_VALUE: [i16; 64] = {
-        let (mut table, mut i) = ([0; 64], 0);
+    #[test]
+    fn huge_weights_saturate_the_score() {
+        let mut params = EvalParams::DEFAULT;
+        params.material[Piece::Queen.to_index()] = (20_000, 20_000);
 
-        while i < 64 {
-            table[i] = ENDGAME_QUEEN_POSITION_VALUE[i] + endgame_material_values(Piece::Queen);
-            i += 1;
+        // Seven queens are worth far more than a centipawn score can hold
+        let fen = "4k3/8/8/8/8/8/QQQQ4/QQQ1K3";
+        for (side, expected) in [("w", i16::MAX), ("b", -i16::MAX)] {
+            let board = Board::from_str(&format!("{} {} - - 0 1", fen, side)).unwrap();
+            assert_eq!(eval_heuristic(&board, &params), Score::cp(expected));
         }
+    }
 
-        table
-    };
-
-    /// Piece table for a king in the midgame, combining its inherent material value and its positional value
-    pub const MIDGAME_KING_VALUE: [i16; 64] = {
-        let (mut table, mut i) = ([0; 64], 0);
-
-        while i < 64 {
-            table[i] = MIDGAME_KING_POSITION_VALUE[i] + midgame_material_values(Piece::King);
-            i += 1;
+    #[test]
+    fn material_only_weights_leave_the_material_balance() {
+        for (fen, expected) in [
+            // A pawn up, with no pieces left, so the endgame pawn value applies in full
+            ("4k3/pppp4/8/8/8/8/PPPPP3/4K3 w - - 0 1", 94),
+            // A knight up, at phase 1 out of 24
+            (
+                "4k3/pppp4/8/8/8/8/PPPP4/1N2K3 w - - 0 1",
+                (337 + 281 * 23) / 24,
+            ),
+        ] {
+            let board = Board::from_str(fen).unwrap();
+            let score = eval_heuristic(&board, &EvalParams::MATERIAL_ONLY);
+            assert_eq!(score, Score::cp(expected), "{}", fen);
         }
+    }
 
-        table
-    };
-
-    /// Piece table for a king in the endgame, combining its inherent material value and its positional value
-    pub const ENDGAME_KING_VALUE: [i16; 64] = {
-        let (mut table, mut i) = ([0; 64], 0);
-
-        while i < 64 {
-            table[i] = ENDGAME_KING_POSITION_VALUE[i] + endgame_material_values(Piece::King);
-            i += 1;
+    #[test]
+    fn insufficient_material_is_recognized() {
+        for (fen, insufficient) in [
+            // Bare kings
+            ("8/8/4k3/8/8/3K4/8/8 w - - 0 1", true),
+            // A lone knight
+            ("8/8/4k3/8/8/3K4/8/6N1 w - - 0 1", true),
+            // A lone bishop
+            ("8/8/4k3/8/8/3K4/8/5b2 w - - 0 1", true),
+            // Bishops on the same colored squares
+            ("8/8/4k3/8/2b5/3K4/8/5B2 w - - 0 1", true),
+            // Bishops on opposite colored squares can still mate (with help)
+            ("8/8/4k3/2b5/8/3K4/8/5B2 w - - 0 1", false),
+            // Bishop and knight is a forced mate
+            ("8/8/4k3/8/8/3K4/8/5BN1 w - - 0 1", false),
+            // A pawn can promote
+            ("8/8/4k3/8/8/3K4/4P3/8 w - - 0 1", false),
+        ] {
+            let board = Board::from_str(fen).unwrap();
+            assert_eq!(is_insufficient_material(&board), insufficient, "{}", fen);
         }
-
-        table
-    };
+    }
 }
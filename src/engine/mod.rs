@@ -1,169 +1,1122 @@
 use std::{
     cmp::Ordering,
     str::FromStr,
+    sync::{
+        Arc, LazyLock,
+        atomic::{AtomicBool, AtomicU64, Ordering as AtomicOrdering},
+        mpsc::{self, Receiver, Sender},
+    },
+    thread,
     time::{Duration, Instant},
 };
 
 use anyhow::Context;
-use chess::{Board, BoardStatus, ChessMove, Color, MoveGen};
-use evaluation::eval_heuristic;
-use parking_lot::RwLock;
-use rayon::iter::{IntoParallelIterator, ParallelBridge, ParallelIterator};
+use chess::{Board, BoardStatus, ChessMove, Color, EMPTY, MoveGen, Piece, Square};
+use evaluation::{
+    EvalParams, MAX_PHASE, eval_heuristic, game_phase, is_insufficient_material, piece_value,
+};
+use move_ordering::{HistoryTable, KillerTable, captured_piece, order_moves};
+use parking_lot::{Mutex, RwLock};
+use rayon::{
+    ThreadPool, ThreadPoolBuilder,
+    iter::{IndexedParallelIterator, IntoParallelIterator, ParallelIterator},
+};
+use see::see;
+use transposition::{
+    DEFAULT_HASH_MB, MAX_HASH_MB, MEGABYTE, NodeType, TranspositionData, TranspositionTable,
+};
 use uci_parser::{UciInfo, UciResponse, UciScore, UciSearchOptions};
+use zobrist::HashScheme;
 
-use crate::score::Score;
+use crate::{score::Score, uci_log};
 
+pub mod chess960;
 pub mod evaluation;
+pub mod move_ordering;
+pub mod options;
+pub mod perft;
+pub mod see;
+pub mod transposition;
+pub mod zobrist;
 
 /// A [`Duration`] subtracted from each move's thinking time, to make sure we don't accidentally go over
 ///
 /// Our time to respond is usually slightly higher than our planned thinking time,
-/// because it takes some time to terminate the search early, and to spit out our answer to `stdout`
-const SLACK_TIME: Duration = Duration::from_millis(20);
+/// because it takes some time to terminate the search early, and to spit out our answer to `stdout`.
+/// Communication delays between us and the GUI are accounted for separately, by [`EngineConfig::move_overhead`].
+const SEARCH_TERMINATION_MARGIN: Duration = Duration::from_millis(10);
+
+/// Default value of [`EngineConfig::move_overhead`]
+pub const DEFAULT_MOVE_OVERHEAD: Duration = Duration::from_millis(10);
+
+/// Estimated number of moves left in the game in the endgame, for time controls without `movestogo` (see [`allocate_time`])
+const SUDDEN_DEATH_MIN_MOVES: u32 = 15;
+
+/// Estimated number of extra moves left in the game in the opening, on top of [`SUDDEN_DEATH_MIN_MOVES`]
+///
+/// The estimate scales down with the game phase, so more of the clock is spent per move as the game simplifies.
+const SUDDEN_DEATH_OPENING_MOVES: u32 = 25;
+
+/// Part of the clock that is never allocated, so that a few fast moves can always be made when low on time
+const TIME_SAFETY_BUFFER: Duration = Duration::from_millis(50);
+
+/// The hard time limit is this many times the soft time limit (see [`allocate_time`]), before it is clamped
+const HARD_LIMIT_FACTOR: u32 = 3;
+
+/// Fraction (numerator, denominator) of the time budget that it is extended by when an iteration is unstable
+///
+/// See [`Engine::extend_soft_stop_time`]
+const TIME_EXTENSION: (u32, u32) = (1, 2);
+
+/// An iteration is unstable if its score falls by more than this many centipawns from the previous iteration's
+const SCORE_DROP_MARGIN: i16 = 50;
+
+/// A single move never gets more than this fraction (numerator, denominator) of the clock, after the safety buffer
+const MAX_TIME_FRACTION: (u32, u32) = (1, 3);
+
+/// Default value of [`EngineConfig::progress_interval`]
+pub const DEFAULT_PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The root move being searched is only reported once the search has been running for this long,
+/// so that short searches don't flood the GUI
+const CURRMOVE_DELAY: Duration = Duration::from_secs(1);
+
+/// Centipawn margin for futility pruning: quiet moves at the frontier are skipped if the static evaluation plus this is below alpha
+const FUTILITY_MARGIN: i16 = 200;
+
+/// Centipawn margin (per remaining ply) for razoring
+const RAZORING_MARGIN: i16 = 300;
+
+/// Razoring is only done when at most this many plies remain before the quiescence search
+const RAZORING_MAX_REMAINING_DEPTH: u8 = 2;
+
+/// Number of plies the null move search is reduced by (on top of the ply for the null move itself)
+const NULL_MOVE_REDUCTION: u8 = 2;
+
+/// Null move pruning is only done when at least this many plies remain before the quiescence search
+const NULL_MOVE_MIN_REMAINING_DEPTH: u8 = 3;
+
+/// Late move reductions are only applied to moves after this many moves have been searched
+const LMR_MIN_MOVE_INDEX: usize = 3;
+
+/// Late move reductions are only applied when at least this many plies remain before the quiescence search
+const LMR_MIN_REMAINING_DEPTH: u8 = 3;
+
+/// Initial distance of the aspiration window from the previous iteration's score, in centipawns
+const ASPIRATION_WINDOW: i16 = 25;
+
+/// Aspiration windows that would need to be wider than this (in centipawns) fall back to the full window
+const ASPIRATION_MAX_WINDOW: i16 = 800;
+
+/// Centipawn margin for delta pruning: captures are skipped in the quiescence search
+/// if standing pat plus the value of the captured piece and this margin is still below alpha
+const DELTA_MARGIN: i16 = 200;
+
+/// Delta pruning is only done when the game phase is at least this
+///
+/// In the late endgame, a single capture can swing the game by much more than the material it wins (e.g. by enabling a promotion)
+const DELTA_PRUNING_MIN_PHASE: i32 = 4;
+
+/// How often a finished search checks whether it is still pondering
+const PONDER_POLL_INTERVAL: Duration = Duration::from_millis(5);
 
-#[derive(Debug, Default)]
+/// Halfmove clock at which the game is drawn by the fifty-move rule
+const FIFTY_MOVE_LIMIT: u32 = 100;
+
+/// Moves other than recapturing are only reduced when at least this many plies remain before the quiescence search
+const RECAPTURE_MIN_REMAINING_DEPTH: u8 = 2;
+
+/// Switches for each of the forward pruning techniques used by the search, and for killer moves
+///
+/// All techniques are enabled by default.
+/// Disabling them one at a time is useful for measuring their contribution (e.g. with the self-play tool).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PruningConfig {
+    /// Null move pruning
+    pub null_move: bool,
+    /// Late move reductions
+    pub lmr: bool,
+    /// Futility pruning
+    pub futility: bool,
+    /// Razoring
+    pub razoring: bool,
+    /// Reducing moves other than recapturing, after being captured
+    pub recapture: bool,
+    /// Delta pruning, in the quiescence search
+    pub delta: bool,
+    /// Skipping captures that lose material (by [`see`]), in the quiescence search
+    pub see: bool,
+    /// Searching killer moves (see [`KillerTable`]) ahead of the other quiet moves
+    ///
+    /// This isn't pruning, but it is switched off the same way to measure what it is worth.
+    pub killers: bool,
+}
+
+impl Default for PruningConfig {
+    fn default() -> Self {
+        Self {
+            null_move: true,
+            lmr: true,
+            futility: true,
+            razoring: true,
+            recapture: true,
+            delta: true,
+            see: true,
+            killers: true,
+        }
+    }
+}
+
+/// Environment variable that pins the maximum number of threads the search may use
+///
+/// This is read once at startup, and is mostly useful for making benchmarks comparable across runs and machines.
+/// If it is unset (or not a positive integer), all available cores are used.
+pub const THREADS_ENV_VAR: &str = "PATCH_THREADS";
+
+/// The maximum number of threads the search may use, read on first use (see [`max_threads`])
+static MAX_THREADS: LazyLock<usize> = LazyLock::new(|| {
+    std::env::var(THREADS_ENV_VAR)
+        .ok()
+        .and_then(|threads| threads.parse::<usize>().ok())
+        .filter(|threads| *threads > 0)
+        .unwrap_or_else(|| {
+            std::thread::available_parallelism()
+                .map(|threads| threads.get())
+                .unwrap_or(1)
+        })
+});
+
+/// The maximum number of threads the search may use
+///
+/// This is the value of [`THREADS_ENV_VAR`] if it is set,
+/// otherwise the available parallelism of the machine.
+pub fn max_threads() -> usize {
+    *MAX_THREADS
+}
+
+/// Memory used by the tables other than the transposition table, in bytes
+///
+/// These share the [`EngineConfig::hash_mb`] budget with the transposition table, which gets whatever is left over.
+const AUXILIARY_TABLES_SIZE: usize = size_of::<KillerTable>() + HistoryTable::MEMORY_USAGE;
+
+/// The transposition table for a budget of `hash_mb` megabytes (see [`AUXILIARY_TABLES_SIZE`])
+fn transposition_table_for(hash_mb: usize) -> TranspositionTable {
+    TranspositionTable::new((hash_mb * MEGABYTE).saturating_sub(AUXILIARY_TABLES_SIZE))
+}
+
+/// Builds a thread pool for the search with `threads` threads
+///
+/// Returns `None` if the pool couldn't be built, in which case the global thread pool is used instead
+fn build_thread_pool(threads: usize) -> Option<ThreadPool> {
+    ThreadPoolBuilder::new().num_threads(threads).build().ok()
+}
+
+/// Shuffles `moves` with a SplitMix64 generator seeded from `seed` (see [`Engine::set_seed`])
+///
+/// The generator is tiny and entirely specified here, so the order only ever depends on the seed
+fn shuffle(moves: &mut [ChessMove], seed: u64) {
+    let mut state = seed;
+    for i in (1..moves.len()).rev() {
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+
+        moves.swap(i, (z % (i as u64 + 1)) as usize);
+    }
+}
+
+/// Thinking time for a tournament time control, where `movestogo` moves must be made before the clock is replenished
+///
+/// The current move gets a bit more than an even share of the time until the next control (including the increments received on the way),
+/// as it is the only move we know we will definitely have to play.
+/// However, some time is always kept in reserve for the remaining moves, so that the final moves before the control aren't starved,
+/// and so that the last move before the control never spends the entire clock.
+fn movestogo_thinking_time(time: Duration, inc: Duration, movestogo: u32) -> Duration {
+    let movestogo = movestogo.max(1);
+
+    // Time available until the next control, including the increments received for the moves after this one
+    let budget = time + inc * (movestogo - 1);
+    let share = budget / movestogo;
+
+    // Keep at least half an even share for each of the remaining moves, and always at least a tenth of the clock
+    let reserve = (time / 10).max(time / movestogo * (movestogo - 1) / 2);
+
+    (share + share / 4).min(time - reserve)
+}
+
+/// Soft and hard thinking time limits for the player up to move on `board`, or `None` if `options` don't give them a clock
+///
+/// No new iteration is started after the soft limit, but the iteration in progress may run on until the hard limit.
+/// The soft limit is the expected thinking time:
+/// - With `movestogo`, the time is split across the moves until the next control (see [`movestogo_thinking_time`])
+/// - Otherwise (sudden death, with or without an increment), the clock is split across an estimate of the moves left in the game,
+///   which shrinks as material comes off the board, and most of the increment is spent on top
+///
+/// The hard limit is [`HARD_LIMIT_FACTOR`] times that.
+/// Either way, [`TIME_SAFETY_BUFFER`] is kept off the clock, and no move gets more than [`MAX_TIME_FRACTION`] of the rest.
+pub fn allocate_time(options: &UciSearchOptions, board: &Board) -> Option<(Duration, Duration)> {
+    let (time, inc) = match board.side_to_move() {
+        Color::White => (options.wtime?, options.winc.unwrap_or_default()),
+        Color::Black => (options.btime?, options.binc.unwrap_or_default()),
+    };
+    let usable = time.saturating_sub(TIME_SAFETY_BUFFER);
+
+    let thinking_time = match options.movestogo {
+        Some(movestogo) => movestogo_thinking_time(usable, inc, movestogo),
+        None => {
+            let moves_left = SUDDEN_DEATH_MIN_MOVES
+                + SUDDEN_DEATH_OPENING_MOVES * game_phase(board) as u32 / MAX_PHASE as u32;
+            usable / moves_left + inc * 3 / 4
+        }
+    };
+
+    let max = usable * MAX_TIME_FRACTION.0 / MAX_TIME_FRACTION.1;
+    Some((
+        thinking_time.min(max),
+        (thinking_time * HARD_LIMIT_FACTOR).min(max),
+    ))
+}
+
+/// User-facing configuration of an [`Engine`]
+///
+/// Unlike the rest of the engine's state, this persists across games (see [`Engine::reset_game`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EngineConfig {
+    /// Whether debugging is enabled
+    pub debug: bool,
+    /// Number of candidate moves to report after each iteration (see [`Engine::set_candidates`])
+    pub candidates: usize,
+    /// Number of best lines to search and report (see [`Engine::set_multi_pv`])
+    pub multi_pv: usize,
+    /// Centipawn penalty for drawing, from our perspective (see [`Engine::set_contempt`])
+    pub contempt: i16,
+    /// Seed that the root moves are shuffled with, if any (see [`Engine::set_seed`])
+    pub seed: Option<u64>,
+    /// Time lost communicating each move with the GUI (see [`Engine::set_move_overhead`])
+    pub move_overhead: Duration,
+    /// Whether the GUI lets us ponder (see [`Engine::set_ponder`])
+    pub ponder: bool,
+    /// Which forward pruning techniques are enabled
+    pub pruning: PruningConfig,
+    /// Weights of the evaluation terms (see [`Engine::set_eval_params`])
+    pub eval_params: EvalParams,
+    /// How positions are hashed (see [`Engine::hash`])
+    pub hash_scheme: HashScheme,
+    /// Number of threads to search with (see [`Engine::set_threads`])
+    pub threads: usize,
+    /// Memory budget for the engine's tables, in megabytes (see [`Engine::set_hash_size`])
+    pub hash_mb: usize,
+    /// Whether castling is written in the Chess960 convention over UCI (see [`Engine::set_chess960`])
+    pub chess960: bool,
+    /// How often to report the current best move during an iteration, if at all
+    ///
+    /// Deep iterations can take a long time, so this lets the GUI show progress between them.
+    pub progress_interval: Option<Duration>,
+}
+
+impl Default for EngineConfig {
+    fn default() -> Self {
+        Self {
+            debug: false,
+            candidates: 0,
+            multi_pv: 1,
+            contempt: 0,
+            seed: None,
+            move_overhead: DEFAULT_MOVE_OVERHEAD,
+            ponder: false,
+            pruning: PruningConfig::default(),
+            eval_params: EvalParams::default(),
+            hash_scheme: HashScheme::default(),
+            threads: max_threads(),
+            hash_mb: DEFAULT_HASH_MB,
+            chess960: false,
+            progress_interval: Some(DEFAULT_PROGRESS_INTERVAL),
+        }
+    }
+}
+
+#[derive(Debug)]
 pub struct Engine {
-    debug: bool,
+    config: EngineConfig,
 
     board: Board,
+    /// Move counters for `board`, which aren't tracked by [`Board`] itself
+    counters: MoveCounters,
+    /// The positions before `board` in the game, since the last pawn move or capture
+    history: Vec<Board>,
+    /// The FEN (`None` for the starting position) and moves that `board` was set up from in [`Self::set_position`]
+    position_moves: Option<(Option<String>, Vec<ChessMove>)>,
+    /// Hashes of `history` for the current search, used to detect repetitions
+    history_hashes: Vec<u64>,
 
     start_time: Option<Instant>,
     stop_time: Option<Instant>,
+    /// No new iteration is started after this time (see [`allocate_time`])
+    soft_stop_time: Option<Instant>,
+    /// Set while pondering until the `ponderhit` is noticed, if ponder time isn't counted (see [`Self::note_ponder_hit`])
+    ponder_pending: AtomicBool,
+    /// Time spent pondering before the `ponderhit`, in microseconds, which the time limits are pushed back by
+    ponder_offset_us: AtomicU64,
     current_search_depth: u8,
     depth_limit: Option<u8>,
+    /// Mate to search for in `go mate`, in moves
+    mate_limit: Option<u32>,
+    /// Number of nodes to search in `go nodes`
+    node_limit: Option<u64>,
+    /// Root moves to search in `go searchmoves`, or empty to search all of them
+    root_moves: Vec<ChessMove>,
     best_move_found: Option<ChessMove>,
+    /// Score of `best_move_found`
+    best_score_found: Option<Score>,
+    /// Depth of the last completed iteration
+    completed_depth: u8,
+    /// Number of nodes visited in the current search
+    nodes: AtomicU64,
+    /// Number of transposition table lookups in the current search
+    tt_probes: AtomicU64,
+    /// Number of transposition table lookups that found an entry in the current search
+    tt_hits: AtomicU64,
+    /// Evaluations of each root move searched in the current iteration
+    root_evals: Mutex<Vec<BoardEvaluation>>,
+    /// Evaluations of each root move from the last completed iteration
+    completed_root_evals: Vec<BoardEvaluation>,
+    /// Whether every root move is searched with the full window, so that all their scores are exact
+    exact_root_scores: bool,
+    /// Subscriber to [`SearchEvent`]s (see [`Self::subscribe`])
+    events: Option<Sender<SearchEvent>>,
+    /// When the last `info` line was printed in the current search
+    last_report: Mutex<Option<Instant>>,
+    /// Results of previously searched positions, kept across searches within a game
+    transposition_table: Arc<RwLock<TranspositionTable>>,
+    /// Set to stop the search in progress (see [`Self::stop_flag`])
+    stop: Arc<AtomicBool>,
+    /// Set while pondering (see [`Self::ponder_flag`])
+    pondering: Arc<AtomicBool>,
+    /// Thread pool that the search runs in, sized by [`EngineConfig::threads`]
+    thread_pool: Option<ThreadPool>,
+    /// Killer moves for the current search, shared between all the search threads
+    killers: RwLock<KillerTable>,
+    /// History scores of quiet moves, kept (and aged) across searches within a game
+    history_table: HistoryTable,
+    /// Player at the root of the searches whose draw scores are in the transposition table, if they include a contempt
+    ///
+    /// The contempt is relative to the root player (see [`Self::draw_score`]),
+    /// so the table is cleared before searching for the other player.
+    contempt_perspective: Option<Color>,
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self::with_config(EngineConfig::default())
+    }
+}
+
+/// Detailed result of a search (see [`Engine::analyze`])
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearchResult {
+    /// The best move found
+    pub best_move: ChessMove,
+    /// The score of the best move
+    pub score: Score,
+    /// The depth of the last completed iteration
+    pub depth: u8,
+    /// The number of nodes searched
+    pub nodes: u64,
+}
+
+/// How long to search each position for in [`Engine::eval_curve`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CurveLimit {
+    /// Search each position to a fixed depth, in plies
+    Depth(u8),
+    /// Search each position for a fixed amount of time
+    MoveTime(Duration),
+}
+
+/// Events sent to the subscriber of an [`Engine`] during the search (see [`Engine::subscribe`])
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchEvent {
+    /// A new root move has overtaken the current best within an iteration
+    BestMoveChanged {
+        /// The new best move
+        mv: ChessMove,
+        /// The score of the new best move (which may only be a bound, until the iteration completes)
+        score: Score,
+        /// The depth of the iteration in progress
+        depth: u8,
+    },
 }
 
 impl Engine {
+    /// Constructs a fully configured engine in one step
+    ///
+    /// This is equivalent to calling each of the setters on a default engine
+    pub fn with_config(config: EngineConfig) -> Self {
+        Self {
+            transposition_table: Arc::new(RwLock::new(transposition_table_for(config.hash_mb))),
+            thread_pool: build_thread_pool(config.threads),
+            config,
+            board: Board::default(),
+            counters: MoveCounters::default(),
+            history: Vec::new(),
+            position_moves: None,
+            history_hashes: Vec::new(),
+            start_time: None,
+            stop_time: None,
+            soft_stop_time: None,
+            ponder_pending: AtomicBool::new(false),
+            ponder_offset_us: AtomicU64::new(0),
+            current_search_depth: 0,
+            depth_limit: None,
+            mate_limit: None,
+            node_limit: None,
+            root_moves: Vec::new(),
+            best_move_found: None,
+            best_score_found: None,
+            completed_depth: 0,
+            nodes: AtomicU64::new(0),
+            tt_probes: AtomicU64::new(0),
+            tt_hits: AtomicU64::new(0),
+            root_evals: Mutex::default(),
+            completed_root_evals: Vec::new(),
+            exact_root_scores: false,
+            events: None,
+            last_report: Mutex::default(),
+            stop: Arc::default(),
+            pondering: Arc::default(),
+            killers: RwLock::default(),
+            history_table: HistoryTable::default(),
+            contempt_perspective: None,
+        }
+    }
+
+    /// The engine's current configuration
+    pub fn config(&self) -> &EngineConfig {
+        &self.config
+    }
+
+    /// The position set up in [`Self::set_position`]
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
     /// Sets the debug flag
     pub fn set_debug(&mut self, debug: bool) {
-        self.debug = debug;
+        self.config.debug = debug;
     }
 
     /// Whether debugging is enabled
     pub fn debug(&self) -> bool {
-        self.debug
+        self.config.debug
+    }
+
+    /// Sends `message` to the GUI as an `info string`, but only if debugging is enabled
+    ///
+    /// The message is only built when it is actually sent, so that diagnostics cost nothing otherwise
+    fn debug_info(&self, message: impl FnOnce() -> String) {
+        if self.config.debug {
+            uci_log::send(UciResponse::info(UciInfo::new().string(message())));
+        }
+    }
+
+    /// Sets the number of candidate moves to report after each iteration
+    ///
+    /// This is a cheap alternative to a true MultiPV search:
+    /// the root moves are ranked from a single search pass, and the top `candidates` are reported.
+    /// Only the best move's score is exact after the pass, as the others were pruned by alpha-beta,
+    /// so the other reported moves are searched again with the full window to get their exact scores.
+    /// A value of zero disables the candidate report, and only the best move is reported.
+    pub fn set_candidates(&mut self, candidates: usize) {
+        self.config.candidates = candidates;
+    }
+
+    /// Sets the number of best lines to search and report after each iteration (MultiPV)
+    ///
+    /// Unlike [`Self::set_candidates`], every root move is searched with the full window when this is above one,
+    /// so all the reported scores are exact, at the cost of a slower search.
+    /// Values below one are treated as one.
+    pub fn set_multi_pv(&mut self, lines: usize) {
+        self.config.multi_pv = lines.max(1);
+    }
+
+    /// Whether every root move is searched with the full window, so that all their scores are exact
+    fn exact_root(&self) -> bool {
+        self.exact_root_scores || self.config.multi_pv > 1
+    }
+
+    /// Sets the contempt for draws, in centipawns
+    ///
+    /// Positive contempt scores draws as slightly losing for us, so we avoid them.
+    /// Negative contempt scores draws as slightly winning for us, so we seek them.
+    /// The contempt is applied in full in the opening, and fades out as material comes off the board,
+    /// as draws become more realistic results in the endgame.
+    ///
+    /// Draws already in the transposition table were scored with the old contempt, so changing it clears the table.
+    pub fn set_contempt(&mut self, contempt: i16) {
+        if contempt != self.config.contempt {
+            self.config.contempt = contempt;
+            self.clear_hash();
+        }
+    }
+
+    /// Sets the seed that the root moves are shuffled with, for reproducible variety in self-play
+    ///
+    /// Before the root moves are ordered, they are shuffled with a generator seeded from `seed` and the position,
+    /// so the seed decides between moves that move ordering ranks equally.
+    /// Different seeds lead to different games, while the same seed always reproduces the same game from the same inputs,
+    /// as long as the search itself is deterministic (with a single thread).
+    /// Without a seed, the moves are ordered as they are generated.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.config.seed = Some(seed);
+    }
+
+    /// Sets whether the GUI lets us ponder
+    ///
+    /// When it does, time spent pondering isn't counted against the move, as the GUI only starts our clock on the `ponderhit`.
+    pub fn set_ponder(&mut self, ponder: bool) {
+        self.config.ponder = ponder;
+    }
+
+    /// Sets the weights used by the evaluation heuristic
+    ///
+    /// Scores stored in the transposition table were computed with the old weights, so it is cleared.
+    pub fn set_eval_params(&mut self, params: EvalParams) {
+        self.config.eval_params = params;
+        self.clear_hash();
+    }
+
+    /// Sets the time lost communicating each move with the GUI
+    ///
+    /// This is subtracted from every move's thinking time, on top of the time needed to terminate the search.
+    /// Increase it if the engine loses on time under laggy conditions (e.g. over a network).
+    pub fn set_move_overhead(&mut self, move_overhead: Duration) {
+        self.config.move_overhead = move_overhead;
+    }
+
+    /// Sets the number of threads to search with
+    ///
+    /// The number is clamped to between 1 and [`max_threads`], so [`THREADS_ENV_VAR`] pins the maximum.
+    /// With a single thread, the search is deterministic, which is useful for debugging.
+    pub fn set_threads(&mut self, threads: usize) {
+        self.config.threads = threads.clamp(1, max_threads());
+        self.thread_pool = build_thread_pool(self.config.threads);
+    }
+
+    /// Sets the memory budget for the engine's tables, in megabytes
+    ///
+    /// The budget is clamped to between 1 MB and [`MAX_HASH_MB`].
+    /// The transposition table gets whatever the other tables leave over (see [`Self::memory_usage`]).
+    /// This replaces the table with a newly allocated one, so everything stored in it is lost.
+    /// If the new table doesn't fit in memory, this fails, and the current table is kept.
+    pub fn set_hash_size(&mut self, megabytes: usize) -> anyhow::Result<()> {
+        let megabytes = megabytes.clamp(1, MAX_HASH_MB);
+        let mut table = transposition_table_for(megabytes);
+        table.allocate().with_context(|| {
+            format!(
+                "Failed to allocate a {} MB hash table, keeping the current {} MB one",
+                megabytes, self.config.hash_mb
+            )
+        })?;
+
+        self.config.hash_mb = megabytes;
+        *self.transposition_table.write() = table;
+        Ok(())
+    }
+
+    /// Removes everything stored in the transposition table
+    pub fn clear_hash(&mut self) {
+        self.transposition_table.write().clear();
+    }
+
+    /// How full the transposition table is, in permille, as reported by the UCI `hashfull` info
+    pub fn hashfull(&self) -> usize {
+        self.transposition_table.read().hashfull()
+    }
+
+    /// Memory used by the engine's tables, in bytes
+    ///
+    /// This counts the transposition table, the killer moves, and the history table,
+    /// which all share the [`EngineConfig::hash_mb`] budget, so this never exceeds it.
+    /// If more tables are added, they must be counted in [`AUXILIARY_TABLES_SIZE`] too.
+    pub fn memory_usage(&self) -> usize {
+        self.transposition_table.read().memory_usage() + AUXILIARY_TABLES_SIZE
+    }
+
+    /// Sets how often the current best move is reported during an iteration, or disables it with `None`
+    pub fn set_progress_interval(&mut self, interval: Option<Duration>) {
+        self.config.progress_interval = interval;
+    }
+
+    /// Sets which forward pruning techniques are enabled
+    pub fn set_pruning(&mut self, pruning: PruningConfig) {
+        self.config.pruning = pruning;
+    }
+
+    /// Sets how positions are hashed
+    ///
+    /// Use [`HashScheme::Zobrist`] when hashes need to be reproducible regardless of the `chess` crate's version
+    pub fn set_hash_scheme(&mut self, hash_scheme: HashScheme) {
+        self.config.hash_scheme = hash_scheme;
+    }
+
+    /// Hash of the current position, using the configured [`HashScheme`]
+    pub fn hash(&self) -> u64 {
+        self.config.hash_scheme.hash(&self.board)
+    }
+
+    /// Sets whether castling is written in the Chess960 convention over UCI (`UCI_Chess960`)
+    ///
+    /// When it is, castling moves are the king capturing its own rook (e.g. `e1h1`),
+    /// and FENs may name the castling rooks by their files (see [`chess960`]).
+    pub fn set_chess960(&mut self, chess960: bool) {
+        self.config.chess960 = chess960;
+    }
+
+    /// `mv` on `board` in UCI notation, with castling written in the configured convention
+    pub fn uci_move(&self, board: &Board, mv: ChessMove) -> String {
+        if self.config.chess960 {
+            chess960::move_to_uci(board, mv).to_string()
+        } else {
+            mv.to_string()
+        }
+    }
+
+    /// Score of a drawn position, from the perspective of the player up to move on `board`
+    ///
+    /// The contempt (scaled by the game phase) is applied from the perspective of the player at the root of the search.
+    /// The root player is up to move on even depths.
+    fn draw_score(&self, board: &Board, depth: u8) -> Score {
+        let contempt = (self.config.contempt as i32 * game_phase(board) / MAX_PHASE) as i16;
+
+        if depth % 2 == 0 {
+            Score::cp(-contempt)
+        } else {
+            Score::cp(contempt)
+        }
     }
 
     /// Resets the internal state for a new game
     ///
-    /// Resets everything except the [`Engine::debug()`] flag
+    /// Resets everything except the [`Engine::config()`] (which includes the [`Engine::debug()`] flag),
+    /// the event subscriber, the [`Engine::stop_flag()`], and the thread pool.
+    /// The tables are cleared in place, as reallocating them would be slow for a large [`EngineConfig::hash_mb`].
     pub fn reset_game(&mut self) {
-        *self = Self {
-            debug: self.debug,
-            ..Default::default()
-        };
+        self.set_position_from_board(Board::default());
+        self.history_hashes.clear();
+        self.reset_search_params();
+        self.clear_hash();
+        self.history_table.clear();
+    }
+
+    /// Subscribes to [`SearchEvent`]s, which are sent as they happen during the search
+    ///
+    /// This is useful for embedders that want to show the search's progress in real time.
+    /// Only the most recent subscriber receives events.
+    /// Subscriptions persist across games.
+    pub fn subscribe(&mut self) -> Receiver<SearchEvent> {
+        let (sender, receiver) = mpsc::channel();
+        self.events = Some(sender);
+        receiver
+    }
+
+    /// Sends `event` to the subscriber, if there is one
+    fn send_event(&self, event: SearchEvent) {
+        if let Some(events) = &self.events {
+            // It's fine if nobody is listening anymore
+            let _ = events.send(event);
+        }
+    }
+
+    /// Flag that stops the search in progress when set, such as on the UCI `stop` command
+    ///
+    /// The search then returns the best move from the last completed iteration.
+    /// The flag is shared with the engine, so it can be set from another thread while the search is running.
+    /// It is never cleared by the engine, so it must be cleared before starting the next search.
+    pub fn stop_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.stop)
+    }
+
+    /// A handle to the ponder flag, which is set while the search is pondering (`go ponder`)
+    ///
+    /// While it is set, the search ignores its time limit, and won't return until the flag is cleared (on `ponderhit`)
+    /// or the search is stopped.
+    /// If [`EngineConfig::ponder`] is set, the time limit counts from the `ponderhit`, so time spent pondering doesn't count towards the move.
+    /// Otherwise, it counts from the start of the search.
+    /// Like the stop flag, it must be set by the caller before starting the search, as that's the only way to do so without racing the `ponderhit`.
+    pub fn ponder_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.pondering)
+    }
+
+    /// The move that we expect the opponent to reply to the best move found with, if any
+    ///
+    /// This is taken from the transposition table, and is reported alongside the best move, so that the GUI knows what to ponder on
+    pub fn ponder_move(&self) -> Option<ChessMove> {
+        let board = self.board.make_move_new(self.best_move_found?);
+
+        self.transposition_table
+            .read()
+            .get(self.config.hash_scheme.hash(&board))
+            .and_then(|t| t.best_move)
+            .filter(|mv| board.legal(*mv))
+    }
+
+    /// Number of nodes visited so far in the current (or most recent) search
+    pub fn nodes(&self) -> u64 {
+        self.nodes.load(AtomicOrdering::Relaxed)
     }
 
     /// Resets internal search parameters and flags for a new search
     ///
-    /// E.g. the best move found, the current search depth, etc.
+    /// E.g. the best move found, the current search depth, the node counter, etc.
+    /// Every per-search counter must be reset here, so that statistics never leak between searches.
+    /// Per-game state (everything else) is left intact, and is only reset by [`Self::reset_game`].
     fn reset_search_params(&mut self) {
         self.start_time = None;
         self.stop_time = None;
+        self.soft_stop_time = None;
+        *self.ponder_pending.get_mut() = false;
+        *self.ponder_offset_us.get_mut() = 0;
         self.current_search_depth = 1;
+        self.depth_limit = None;
+        self.mate_limit = None;
+        self.node_limit = None;
+        self.root_moves.clear();
         self.best_move_found = None;
+        self.best_score_found = None;
+        self.completed_depth = 0;
+        self.nodes.store(0, AtomicOrdering::Relaxed);
+        self.tt_probes.store(0, AtomicOrdering::Relaxed);
+        self.tt_hits.store(0, AtomicOrdering::Relaxed);
+        self.root_evals.get_mut().clear();
+        self.completed_root_evals.clear();
+        *self.last_report.get_mut() = None;
+        self.killers.get_mut().clear();
+        self.history_table.age();
+
+        let perspective = (self.config.contempt != 0).then(|| self.board.side_to_move());
+        if perspective != self.contempt_perspective {
+            self.clear_hash();
+            self.contempt_perspective = perspective;
+        }
     }
 
     /// Sets the board to the given position
     ///
-    /// If `fen` is `None`, then the default starting position is used
-    /// TODO: Try to reuse the existing board if possible?
+    /// If `fen` is `None`, then the default starting position is used.
+    /// If any of the moves are illegal, then an error is returned, and the engine's position is left unchanged.
+    ///
+    /// GUIs usually send the whole game again on every move, so if this continues the last position that was set
+    /// (same `fen`, and the previous moves followed by some new ones), then only the new moves are made.
     pub fn set_position(
         &mut self,
         fen: Option<&str>,
         moves: impl Iterator<Item = ChessMove>,
     ) -> Result<(), anyhow::Error> {
+        let fen = fen.map(str::to_owned);
+        let moves: Vec<ChessMove> = moves.collect();
+
+        let played = self
+            .position_moves
+            .as_ref()
+            .filter(|(last_fen, last_moves)| *last_fen == fen && moves.starts_with(last_moves))
+            .map(|(_, last_moves)| last_moves.len());
+
         // Setup board
-        let mut board = if let Some(fen) = fen {
-            Board::from_str(fen).map_err(|e| anyhow::Error::msg(e))?
+        let (mut board, mut counters, mut history, played) = if let Some(played) = played {
+            (self.board, self.counters, self.history.clone(), played)
+        } else if let Some(fen) = &fen {
+            let board_fen = if self.config.chess960 {
+                chess960::standard_fen(fen)?
+            } else {
+                fen.clone()
+            };
+            (
+                Board::from_str(&board_fen).map_err(|e| anyhow::Error::msg(e))?,
+                MoveCounters::from_fen(fen)?,
+                Vec::new(),
+                0,
+            )
         } else {
-            Board::default()
+            (Board::default(), MoveCounters::default(), Vec::new(), 0)
         };
 
-        moves.for_each(|mv| board = board.make_move_new(mv));
-        self.board = board;
+        for mv in moves[played..].iter().copied() {
+            let mv = if self.config.chess960 {
+                chess960::move_from_uci(&board, mv)
+            } else {
+                mv
+            };
+            anyhow::ensure!(board.legal(mv), "Illegal move {} in position {}", mv, board);
 
-        // Clean up for the upcoming search
-        // We do this here, because we're allowed to block while setting up,
-        // and this way we don't use up precious search time
-        self.reset_search_params();
+            counters.update(&board, mv);
+            history.push(board);
+            board = board.make_move_new(mv);
+
+            // Positions from before a pawn move or capture can never occur again
+            if counters.halfmove_clock == 0 {
+                history.clear();
+            }
+        }
+        self.board = board;
+        self.counters = counters;
+        self.history = history;
+        self.position_moves = Some((fen, moves));
 
         Ok(())
     }
 
+    /// Sets the board to the given position directly
+    ///
+    /// As the board doesn't carry the move counters or the history of the game, they are reset
+    pub fn set_position_from_board(&mut self, board: Board) {
+        self.board = board;
+        self.counters = MoveCounters::default();
+        self.history.clear();
+        self.position_moves = None;
+    }
+
+    /// The FEN of the position setup in [`Engine::set_position`], including the move counters
+    pub fn current_fen(&self) -> String {
+        // The board doesn't track the move counters, so we only take the first four fields from it
+        let mut fen = self
+            .board
+            .to_string()
+            .split_whitespace()
+            .take(4)
+            .collect::<Vec<_>>()
+            .join(" ");
+        fen.push_str(&format!(
+            " {} {}",
+            self.counters.halfmove_clock, self.counters.fullmove_number
+        ));
+
+        fen
+    }
+
     /// Determines `self.stop_time` based on the provided options
     ///
     /// This may be a NOP if the options do not indicate that a stop time should be set:
     /// e.g. if the movetime is infinite.
     /// (Actually resetting the stop time to [`None`] is handled in [`Self::reset_search_params`].)
     /// In cases where a `stop_time` is calculated,
-    /// [`SLACK_TIME`] milliseconds is subtracted, to account for tree termination and writing the output
+    /// [`SEARCH_TERMINATION_MARGIN`] and the configured [`EngineConfig::move_overhead`] are subtracted,
+    /// to account for tree termination, writing the output, and communication with the GUI
     ///
     /// - If a finite movetime is specified, then that is used
-    /// - Otherwise, if remaining time and increments are specified, then those are used to determine a reasonable thinking time
-    /// - Otherwise, if moves to go is specified, then that + the remaining time is used to determine a reasonable thinking time
-    /// - Otherwise, it will either panic (unimplemented), or set the `self.stop_time` to [`None`]
+    /// - Otherwise, if we have a clock, then [`allocate_time`] determines a reasonable thinking time
+    /// - Otherwise, there is no time limit, and `self.stop_time` is left as [`None`].
+    ///   The search is then bounded by its depth, mate, or node limit, or runs until stopped (like `go infinite`) if it has none
     #[inline]
     pub fn calculate_stop_time(&mut self, options: &UciSearchOptions) -> anyhow::Result<()> {
+        // Always track when we started, so that progress can be reported even in infinite mode
+        self.start_time = Some(Instant::now());
+
         if !options.infinite {
             // In infinite mode, we search until told to stop
             // Otherwise, we figure out our time control
 
-            self.start_time = Some(Instant::now());
-
             if let Some(movetime) = options.movetime {
                 // Search for the provided duration
                 self.stop_time = Some(
                     self.start_time
                         .unwrap() // Just set above
-                        .checked_add(movetime - SLACK_TIME)
+                        .checked_add(movetime.saturating_sub(self.time_margin()))
                         .context("Failed to add provided movetime to current instant")?,
                 );
-            } else {
-                let (time, inc) = match self.board.side_to_move() {
-                    Color::White => (options.wtime, options.winc),
-                    Color::Black => (options.btime, options.binc),
-                };
+            } else if let Some((soft, hard)) = allocate_time(options, &self.board) {
+                let start_time = self.start_time.unwrap(); // Just set above
+                self.soft_stop_time = Some(
+                    start_time
+                        .checked_add(soft.saturating_sub(self.time_margin()))
+                        .context("Failed to add thinking time to current instant")?,
+                );
+                self.stop_time = Some(
+                    start_time
+                        .checked_add(hard.saturating_sub(self.time_margin()))
+                        .context("Failed to add thinking time to current instant")?,
+                );
+            }
+            // Otherwise, the search is bounded by something other than time (if at all), so we search until we hit that bound
+        }
 
-                if let Some(time) = time {
-                    // Basic thinking time hueristic
-                    let thinking_time = if let Some(movestogo) = options.movestogo {
-                        time / movestogo
-                    } else if let Some(inc) = inc {
-                        time / 20 + inc / 2
-                    } else {
-                        unimplemented!("Got unimplemented time control options");
-                    };
+        self.debug_info(
+            || match (self.start_time, self.soft_stop_time, self.stop_time) {
+                (Some(start), Some(soft), Some(hard)) => format!(
+                    "time budget {}ms (hard limit {}ms)",
+                    (soft - start).as_millis(),
+                    (hard - start).as_millis()
+                ),
+                (Some(start), None, Some(hard)) => {
+                    format!("time budget {}ms", (hard - start).as_millis())
+                }
+                _ => "no time budget".to_string(),
+            },
+        );
 
-                    self.stop_time = Some(
-                        self.start_time
-                            .unwrap() // Just set above
-                            .checked_add(thinking_time - SLACK_TIME)
-                            .context("Failed to add thinking time to current instant")?,
-                    );
-                } else {
-                    unimplemented!("Got unimplemented time control options");
+        Ok(())
+    }
+
+    /// Searches the root to the current depth, inside an aspiration window around the previous iteration's score
+    ///
+    /// The score rarely changes much from one iteration to the next, so a narrow window prunes a lot more.
+    /// If the score falls outside of the window, then that side is widened and the root is searched again.
+    /// Mate scores, and the first iteration, use the full window.
+    fn search_root(&mut self) -> BoardEvaluation {
+        let full = (Score::min_negatable(), Score::max());
+        let mut delta = ASPIRATION_WINDOW;
+        let (mut alpha, mut beta) = match self.best_score_found {
+            Some(Score::Centipawns(cp)) if !self.exact_root() => (
+                Score::cp(cp.saturating_sub(delta)),
+                Score::cp(cp.saturating_add(delta)),
+            ),
+            _ => full,
+        };
+
+        loop {
+            self.root_evals.get_mut().clear();
+            let eval = self.in_thread_pool(|| {
+                self.evaluate_board(&self.board, alpha, beta, 0, self.current_search_depth, None)
+            });
+
+            if eval.terminated_early {
+                return eval;
+            }
+
+            delta = delta.saturating_mul(2);
+            let widened = |bound: i16| (delta <= ASPIRATION_MAX_WINDOW).then_some(Score::cp(bound));
+
+            match eval.score {
+                score if score <= alpha && alpha != full.0 => {
+                    alpha = match score {
+                        Score::Centipawns(cp) => widened(cp.saturating_sub(delta)),
+                        Score::Mate(_) => None,
+                    }
+                    .unwrap_or(full.0);
+                }
+                score if score >= beta && beta != full.1 => {
+                    beta = match score {
+                        Score::Centipawns(cp) => widened(cp.saturating_add(delta)),
+                        Score::Mate(_) => None,
+                    }
+                    .unwrap_or(full.1);
                 }
+                _ => return eval,
             }
         }
+    }
 
-        Ok(())
+    /// Searches each of the `candidates` but the first (the best move) again with the full window, so that their scores are exact
+    ///
+    /// In the candidate report, the other root moves were only searched to prove that they are worse than the best move,
+    /// so their scores are just bounds. Only the few reported moves are searched again, which is what keeps this cheaper than MultiPV.
+    /// Candidates whose search is stopped partway through are left out, and the rest are sorted by their new scores.
+    fn exact_candidates(&self, candidates: Vec<BoardEvaluation>) -> Vec<BoardEvaluation> {
+        let mut candidates = candidates.into_iter();
+        let Some(best) = candidates.next() else {
+            return Vec::new();
+        };
+
+        let hash = self.config.hash_scheme.hash(&self.board);
+        let mut others: Vec<_> = candidates
+            .filter_map(|candidate| {
+                let mv = candidate.mv?;
+                let next = self.board.make_move_new(mv);
+                let path = SearchPath::new(
+                    hash,
+                    None,
+                    false,
+                    is_capture(&self.board, mv).then_some(mv.get_dest()),
+                    halfmove_clock_after(&self.board, mv, self.counters.halfmove_clock),
+                );
+
+                // The full root window, from the child's perspective
+                let eval = BoardEvaluation::from_child(
+                    self.in_thread_pool(|| {
+                        self.evaluate_board(
+                            &next,
+                            Score::max().unflip(),
+                            Score::min_negatable().unflip(),
+                            1,
+                            self.current_search_depth,
+                            Some(&path),
+                        )
+                    }),
+                    mv,
+                );
+                (!eval.terminated_early).then_some(eval)
+            })
+            .collect();
+        others.sort_by(|a, b| b.cmp(a));
+
+        std::iter::once(best).chain(others).collect()
+    }
+
+    /// Total time subtracted from each move's thinking time (see [`Self::calculate_stop_time`])
+    fn time_margin(&self) -> Duration {
+        SEARCH_TERMINATION_MARGIN + self.config.move_overhead
     }
 
     /// Searches for the best move on the position setup in [`Engine::set_position`]
     ///
     /// If [`Engine::set_position`] is not called, then the default chess starting position is used
     pub fn search(&mut self, options: UciSearchOptions) -> anyhow::Result<ChessMove> {
+        // Clean up after any previous search
+        // This is done on every search (rather than when the position is set),
+        // so that repeated searches on the same position each start fresh
+        self.reset_search_params();
+
         // Determine and set stop time
         self.calculate_stop_time(&options)?;
+        *self.ponder_pending.get_mut() =
+            self.config.ponder && self.pondering.load(AtomicOrdering::Relaxed);
 
         // Set depth limit if provided
-        self.depth_limit = options.depth.as_ref().map(|d| *d as u8);
+        // A mate in `x` moves is found within `2x - 1` plies, so there's no point in searching any deeper in `go mate`
+        let mate_depth = options.mate.map(|moves| {
+            moves
+                .saturating_mul(2)
+                .saturating_sub(1)
+                .clamp(1, u8::MAX as u32)
+        });
+        self.depth_limit = [options.depth, mate_depth]
+            .into_iter()
+            .flatten()
+            .min()
+            .map(|d| d.min(u8::MAX as u32) as u8);
+        self.mate_limit = options.mate;
+        self.node_limit = options.nodes.map(u64::from);
+        self.root_moves = options
+            .searchmoves
+            .iter()
+            .map(|mv| {
+                ChessMove::from_str(mv)
+                    .ok()
+                    .filter(|mv| self.board.legal(*mv))
+                    .with_context(|| format!("Illegal move {} in searchmoves", mv))
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        self.history_hashes = self
+            .history
+            .iter()
+            .map(|board| self.config.hash_scheme.hash(board))
+            .collect();
 
         // Search
+        let mut previous_iteration_nodes = 0;
         loop {
-            let eval = self.evaluate_board(&self.board, Score::min_negatable(), Score::max(), 0);
+            // An iteration started after the soft limit would rarely finish before the hard limit, so it isn't started at all
+            if self.best_move_found.is_some() && self.past_soft_stop_time() {
+                self.debug_info(|| "stopped: soft time limit".to_string());
+                return self
+                    .best_move_found
+                    .context("Failed to search even a single depth level");
+            }
+
+            let iteration_start_nodes = self.nodes();
+            let eval = self.search_root();
 
             if !eval.terminated_early {
                 let eval_mv = eval.mv.context(format!(
@@ -171,33 +1124,121 @@ impl Engine {
                     self.board, eval,
                 ))?;
 
+                self.completed_root_evals = std::mem::take(self.root_evals.get_mut());
+                *self.last_report.get_mut() = Some(Instant::now());
+
                 let search_time_ms = self
                     .start_time
                     .map(|start_time| (Instant::now() - start_time).as_millis())
                     .unwrap_or_default();
+                let nodes = self.nodes();
+                // Guard against dividing by zero on very fast iterations
+                let nps = nodes as u128 * 1000 / search_time_ms.max(1);
+                let hashfull = self.hashfull();
 
-                println!(
-                    "{}",
-                    UciResponse::info(
+                let lines = if self.config.multi_pv > 1 {
+                    self.config.multi_pv
+                } else {
+                    self.config.candidates
+                };
+
+                if lines == 0 {
+                    uci_log::send(UciResponse::info(
                         UciInfo::new()
                             .score(UciScore::from(eval.score))
-                            .pv([eval_mv.to_string()])
+                            .pv(self.principal_variation(eval_mv))
                             .depth(self.current_search_depth)
                             .seldepth(eval.depth)
-                            .time(search_time_ms)
+                            .nodes(nodes)
+                            .nps(nps)
+                            .hashfull(hashfull)
+                            .time(search_time_ms),
+                    ));
+                } else {
+                    // Report the best few root moves from this pass (MultiPV, or candidates)
+                    let mut root_evals = self.completed_root_evals.clone();
+                    root_evals.sort_by(|a, b| b.cmp(a));
+                    root_evals.truncate(lines);
+                    if !self.exact_root() {
+                        root_evals = self.exact_candidates(root_evals);
+                    }
+
+                    for (i, candidate) in root_evals.iter().enumerate() {
+                        let Some(mv) = candidate.mv else { continue };
+
+                        uci_log::send(UciResponse::info(
+                            UciInfo::new()
+                                .multipv(i + 1)
+                                .score(UciScore::from(candidate.score))
+                                .pv(self.principal_variation(mv))
+                                .depth(self.current_search_depth)
+                                .seldepth(candidate.depth)
+                                .nodes(nodes)
+                                .nps(nps)
+                                .hashfull(hashfull)
+                                .time(search_time_ms),
+                        ));
+                    }
+                }
+
+                // A search score far from the static eval means there are deep tactics in the position
+                self.debug_info(|| {
+                    format!(
+                        "phase {}/{} static eval {} search score {}",
+                        game_phase(&self.board),
+                        MAX_PHASE,
+                        UciScore::from(eval_heuristic(&self.board, &self.config.eval_params)),
+                        UciScore::from(eval.score),
                     )
-                );
+                });
+                // The branching factor is how many times more nodes this iteration took than the last one
+                self.debug_info(|| {
+                    let probes = self.tt_probes.load(AtomicOrdering::Relaxed);
+                    let hits = self.tt_hits.load(AtomicOrdering::Relaxed);
+                    let iteration_nodes = nodes - iteration_start_nodes;
+                    format!(
+                        "depth {} tt hit rate {:.1}% branching factor {:.2}",
+                        self.current_search_depth,
+                        hits as f64 * 100.0 / probes.max(1) as f64,
+                        iteration_nodes as f64 / previous_iteration_nodes.max(1) as f64,
+                    )
+                });
+                previous_iteration_nodes = nodes - iteration_start_nodes;
+
+                // A new best move, or a falling score, means the position isn't resolved yet, so we take more time to settle it
+                let best_move_changed = self.best_move_found.is_some_and(|mv| mv != eval_mv);
+                let score_dropped = self
+                    .best_score_found
+                    .is_some_and(|score| below_with_margin(eval.score, SCORE_DROP_MARGIN, score));
+                if best_move_changed || score_dropped {
+                    self.extend_soft_stop_time();
+                }
 
-                // TODO: we can still do this on early termination if the tree search is ordered based on previous search depths
-                // TODO: handle stop command if stop_time is None
                 self.best_move_found = Some(eval_mv);
+                self.best_score_found = Some(eval.score);
+                self.completed_depth = self.current_search_depth;
 
-                if self
-                    .depth_limit
-                    .map(|l| l == self.current_search_depth)
-                    .unwrap_or_default()
+                // In `go mate`, we're done as soon as we find a short enough mate.
+                // Otherwise, a shorter mate might still be found by searching deeper, so we keep going.
+                let mate_found = self.mate_limit.is_some_and(|limit| {
+                    matches!(eval.score, Score::Mate(plies) if plies > 0 && (plies as u32 + 1) / 2 <= limit)
+                });
+
+                if mate_found
+                    || self
+                        .depth_limit
+                        .map(|l| l == self.current_search_depth)
+                        .unwrap_or_default()
                 {
-                    // Early termination on depth limit
+                    // Early termination on depth limit, or on finding the requested mate
+                    self.debug_info(|| {
+                        if mate_found {
+                            "stopped: mate found".to_string()
+                        } else {
+                            "stopped: depth limit".to_string()
+                        }
+                    });
+                    self.wait_while_pondering();
                     return self
                         .best_move_found
                         .context("Failed to search even a single depth level");
@@ -207,145 +1248,901 @@ impl Engine {
                 }
             } else {
                 // We are done here
+                self.debug_info(|| format!("stopped: {}", self.stop_reason()));
+                // The interrupted iteration may still have proven that another move is better than the last completed one's
+                if let Some(improved) = self.interrupted_improvement() {
+                    self.best_move_found = improved.mv;
+                    self.best_score_found = Some(improved.score);
+                }
+                // If we were stopped before even the first iteration completed,
+                // the best move we found so far is still better than nothing.
+                // The root always searches at least one move, so there is one, however little time we had
                 return self
                     .best_move_found
+                    .or(eval.mv)
                     .context("Failed to search even a single depth level");
             }
         }
     }
 
+    /// Searches for the best move on the position setup in [`Engine::set_position`], like [`Engine::search`],
+    /// but returns more details about the result
+    pub fn analyze(&mut self, options: UciSearchOptions) -> anyhow::Result<SearchResult> {
+        let best_move = self.search(options)?;
+
+        Ok(SearchResult {
+            best_move,
+            score: self
+                .best_score_found
+                .context("Failed to search even a single depth level")?,
+            depth: self.completed_depth,
+            nodes: self.nodes(),
+        })
+    }
+
+    /// Analyzes each of the provided positions in turn, returning one result per position
+    ///
+    /// This is useful for comparing a family of related positions, such as all the responses to a candidate move.
+    /// Each position is searched with the same `options`.
+    /// The engine's own position is restored afterwards.
+    pub fn analyze_many(
+        &mut self,
+        positions: Vec<Board>,
+        options: UciSearchOptions,
+    ) -> anyhow::Result<Vec<SearchResult>> {
+        let (board, counters, history, position_moves) = (
+            self.board,
+            self.counters,
+            self.history.clone(),
+            self.position_moves.clone(),
+        );
+
+        let results = positions
+            .into_iter()
+            .map(|position| {
+                self.set_position_from_board(position);
+                self.analyze(options.clone())
+            })
+            .collect();
+
+        self.board = board;
+        self.counters = counters;
+        self.history = history;
+        self.position_moves = position_moves;
+
+        results
+    }
+
+    /// Evaluates the position after each move of a game, such as for plotting an evaluation curve in a game review
+    ///
+    /// The game starts from `fen` (or the starting position if it is `None`), like in [`Engine::set_position`].
+    /// Returns one `(ply, score)` entry per move, where `ply` counts the moves made so far (starting at 1).
+    /// Like all scores, each score is from the perspective of the player up to move after that ply.
+    /// Positions where the game is over aren't searched: checkmates score M0, and stalemates score a draw.
+    /// The engine's own position is restored afterwards.
+    pub fn eval_curve(
+        &mut self,
+        fen: Option<&str>,
+        moves: &[ChessMove],
+        limit: CurveLimit,
+    ) -> anyhow::Result<Vec<(usize, Score)>> {
+        let (board, counters, history, position_moves) = (
+            self.board,
+            self.counters,
+            self.history.clone(),
+            self.position_moves.clone(),
+        );
+
+        let options = match limit {
+            CurveLimit::Depth(depth) => UciSearchOptions {
+                depth: Some(depth as u32),
+                infinite: true,
+                ..Default::default()
+            },
+            CurveLimit::MoveTime(movetime) => UciSearchOptions {
+                movetime: Some(movetime),
+                ..Default::default()
+            },
+        };
+
+        let curve = (1..=moves.len())
+            .map(|ply| {
+                self.set_position(fen, moves[..ply].iter().copied())?;
+
+                let score = match self.board.status() {
+                    BoardStatus::Checkmate => Score::Mate(0),
+                    BoardStatus::Stalemate => self.draw_score(&self.board, 0),
+                    BoardStatus::Ongoing => self.analyze(options.clone())?.score,
+                };
+
+                Ok((ply, score))
+            })
+            .collect();
+
+        self.board = board;
+        self.counters = counters;
+        self.history = history;
+        self.position_moves = position_moves;
+
+        curve
+    }
+
+    /// Searches every legal move on the position setup in [`Engine::set_position`], returning them with their scores
+    ///
+    /// Moves are sorted best-first.
+    /// This is a single search, but no root move is pruned, so the scores of all the moves are exact (for the depth reached).
+    /// The scores come from the last completed iteration of the search.
+    pub fn rank_moves(
+        &mut self,
+        options: UciSearchOptions,
+    ) -> anyhow::Result<Vec<(ChessMove, Score)>> {
+        self.exact_root_scores = true;
+        let result = self.search(options);
+        self.exact_root_scores = false;
+        result?;
+
+        let mut root_evals = self.completed_root_evals.clone();
+        root_evals.sort_by(|a, b| b.cmp(a));
+
+        Ok(root_evals
+            .into_iter()
+            .filter_map(|eval| eval.mv.map(|mv| (mv, eval.score)))
+            .collect())
+    }
+
+    /// Runs only a quiescence search on the position setup in [`Engine::set_position`], returning its score
+    ///
+    /// No main-search deepening is performed, so this is a cheap way to check whether a position is tactically stable:
+    /// if the result differs a lot from [`eval_heuristic`], then there are captures worth resolving.
+    /// The time control in `options` is respected, just like in [`Engine::search`].
+    pub fn quiescence_eval(&mut self, options: &UciSearchOptions) -> anyhow::Result<Score> {
+        self.reset_search_params();
+        self.calculate_stop_time(options)?;
+
+        let eval = self.in_thread_pool(|| {
+            self.evaluate_board_quiescence(
+                &self.board,
+                Score::min_negatable(),
+                Score::max(),
+                0,
+                self.counters.halfmove_clock,
+            )
+        });
+
+        Ok(eval.score)
+    }
+
+    /// Runs `op` in the engine's thread pool, so that the search uses the configured number of threads
+    ///
+    /// If the pool couldn't be built, the global thread pool is used instead
+    fn in_thread_pool<R: Send>(&self, op: impl FnOnce() -> R + Send) -> R {
+        match &self.thread_pool {
+            Some(pool) => pool.install(op),
+            None => op(),
+        }
+    }
+
     /// Evaluates the provided board, assuming we are up to move
     ///
     /// Branches based on moves if possible.
     /// Returns the score for this position, and the best move it found,
     /// as long as we are not in a terminal case (recursion limit, stalemate, or checkmate).
+    ///
+    /// `depth` is the number of plies from the root to `board`,
+    /// and `max_depth` is the depth at which this subtree drops into the quiescence search.
+    /// `max_depth` starts at the current iterative deepening depth, and is lowered for reduced subtrees.
     fn evaluate_board(
         &self,
         board: &Board,
         alpha: Score,
         beta: Score,
         depth: u8,
+        max_depth: u8,
+        path: Option<&SearchPath>,
     ) -> BoardEvaluation {
+        self.nodes.fetch_add(1, AtomicOrdering::Relaxed);
+
         match board.status() {
             BoardStatus::Checkmate => {
                 // We lost :(
                 BoardEvaluation::score(Score::Mate(0), depth)
             }
-            BoardStatus::Stalemate => BoardEvaluation::score(Score::cp(0), depth),
+            BoardStatus::Stalemate => BoardEvaluation::score(self.draw_score(board, depth), depth),
             BoardStatus::Ongoing => {
-                if depth == self.current_search_depth {
+                // Repeating a position means that neither side could make progress from it,
+                // so it's scored as a draw right away. This is what lets us find (and avoid) perpetual checks.
+                // Positions from before the root only count on their third occurrence, like in the real game.
+                let hash = self.config.hash_scheme.hash(board);
+                let halfmove_clock = path.map_or(self.counters.halfmove_clock, |path| {
+                    path.halfmove_clock_after
+                });
+                let repeated = path.is_some_and(|path| path.repeats(hash))
+                    || (depth > 0
+                        && self
+                            .history_hashes
+                            .iter()
+                            .filter(|previous| **previous == hash)
+                            .count()
+                            >= 2);
+                // Likewise for the fifty-move rule (checkmates still take priority, and are handled above),
+                // and for positions where neither side can ever checkmate
+                let fifty_moves = depth > 0 && halfmove_clock >= FIFTY_MOVE_LIMIT;
+                let dead = depth > 0 && is_insufficient_material(board);
+                if repeated || fifty_moves || dead {
+                    return BoardEvaluation::score(self.draw_score(board, depth), depth);
+                }
+
+                if depth >= max_depth {
                     // Terminate at max depth
                     // Hueristic based on material
-                    self.evaluate_board_quiescence(board, alpha, beta, depth)
-                } else if self
-                    .stop_time
-                    .map(|st| Instant::now() > st)
-                    .unwrap_or_default()
-                {
-                    // Early termination on time
+                    self.evaluate_board_quiescence(board, alpha, beta, depth, halfmove_clock)
+                } else if depth > 0 && self.should_stop() {
+                    // Early termination on time, or on the stop command
+                    // The root is never cut short, so that even a search stopped right away has a move to play:
+                    // its children return straight away, so this costs next to nothing
                     // Hueristic based on material
-                    BoardEvaluation::score_early(eval_heuristic(board), depth)
+                    BoardEvaluation::score_early(
+                        scaled_eval(board, halfmove_clock, &self.config.eval_params),
+                        depth,
+                    )
                 } else {
+                    let remaining = max_depth - depth;
+                    let in_check = *board.checkers() != EMPTY;
+                    let pruning = &self.config.pruning;
+
+                    // The root always needs a full search, to report on and rank every root move
+                    let transposition = self.transposition_table.read().get(hash);
+                    self.tt_probes.fetch_add(1, AtomicOrdering::Relaxed);
+                    if transposition.is_some() {
+                        self.tt_hits.fetch_add(1, AtomicOrdering::Relaxed);
+                    }
+                    if depth > 0 {
+                        if let Some(eval) =
+                            probe_transposition(transposition, alpha, beta, depth, remaining)
+                        {
+                            return eval;
+                        }
+                    }
+
+                    // Forward pruning is never done at the root, or when in check
+                    let prunable = depth > 0 && !in_check;
+                    let static_eval = scaled_eval(board, halfmove_clock, &self.config.eval_params);
+
+                    if prunable && pruning.razoring {
+                        if let Some(eval) =
+                            self.razor(board, static_eval, alpha, depth, remaining, halfmove_clock)
+                        {
+                            return eval;
+                        }
+                    }
+                    // Two null moves in a row would just skip a ply of the search
+                    let after_null_move = path.is_some_and(|path| path.null_move);
+                    if prunable && pruning.null_move && !after_null_move {
+                        if let Some(eval) = self.null_move_prune(
+                            board,
+                            static_eval,
+                            beta,
+                            depth,
+                            max_depth,
+                            &SearchPath::new(hash, path, true, None, halfmove_clock + 1),
+                        ) {
+                            return eval;
+                        }
+                    }
+
+                    // At the frontier, quiet moves can't raise a hopeless static evaluation above alpha
+                    let futile = prunable
+                        && pruning.futility
+                        && remaining == 1
+                        && below_with_margin(static_eval, FUTILITY_MARGIN, alpha);
+
                     // Down the tree we go
-                    let mut iter = MoveGen::new_legal(board);
+                    let mut moves: Vec<_> = MoveGen::new_legal(board).collect();
+                    // Only the root is restricted by `searchmoves`
+                    let restricted = depth == 0 && !self.root_moves.is_empty();
+                    if restricted {
+                        moves.retain(|mv| self.root_moves.contains(mv));
+                    }
+                    if let Some(seed) = self.config.seed.filter(|_| depth == 0) {
+                        shuffle(&mut moves, seed ^ board.get_hash());
+                    }
+                    let killers = if self.config.pruning.killers {
+                        self.killers.read().get(depth)
+                    } else {
+                        [None; 2]
+                    };
+                    order_moves(board, &mut moves, killers, &self.history_table);
+
+                    // If we were just captured, and we can take back, then taking back is usually our only sensible move
+                    let recapture_square = path
+                        .and_then(|path| path.captured_on)
+                        .filter(|square| moves.iter().any(|mv| mv.get_dest() == *square));
+                    if let Some(square) = recapture_square {
+                        moves.sort_by_key(|mv| mv.get_dest() != square);
+                    }
+
+                    // The best move from a previous search of this position is the most likely to be best again
+                    if let Some(tt_move) = transposition.and_then(|t| t.best_move) {
+                        if let Some(i) = moves.iter().position(|mv| *mv == tt_move) {
+                            moves[..=i].rotate_right(1);
+                        }
+                    }
+
+                    // At the root, the last completed iteration's best move always goes first, even if its table entry was replaced,
+                    // so that an interrupted iteration has an up to date score for it to compare the other moves against
+                    if let Some(previous_best) = self.best_move_found.filter(|_| depth == 0) {
+                        if let Some(i) = moves.iter().position(|mv| *mv == previous_best) {
+                            moves[..=i].rotate_right(1);
+                        }
+                    }
+
+                    // When ranking root moves, every one of them is searched with the initial window,
+                    // and the root never cuts off, so that every root move gets an exact score
+                    let exact = depth == 0 && self.exact_root();
+                    let window_alpha = alpha;
 
-                    let best = RwLock::new(BoardEvaluation::min());
+                    // `None` is the identity here, as it is lower than every real evaluation,
+                    // including a line where we get checkmated
+                    let best: RwLock<Option<BoardEvaluation>> = RwLock::new(None);
                     let alpha = RwLock::new(alpha);
 
-                    // This will always return some non-identity value,
-                    // as long as the above iterator has at least one valid move.
+                    // Searches a single move, returning the evaluation to cut off with if it reaches beta
+                    let search_move = |(i, mv): (usize, ChessMove)| {
+                        if depth == 0 {
+                            self.report_current_move(mv, i + 1);
+                        }
+
+                        let next = board.make_move_new(mv);
+                        let capture = is_capture(board, mv);
+                        let quiet =
+                            !capture && mv.get_promotion().is_none() && *next.checkers() == EMPTY;
+                        let child_path = SearchPath::new(
+                            hash,
+                            path,
+                            false,
+                            capture.then_some(mv.get_dest()),
+                            halfmove_clock_after(board, mv, halfmove_clock),
+                        );
+
+                        // Late quiet moves are unlikely to be good,
+                        // and neither is anything other than recapturing after being captured
+                        let late_move = pruning.lmr
+                            && quiet
+                            && i >= LMR_MIN_MOVE_INDEX
+                            && remaining >= LMR_MIN_REMAINING_DEPTH;
+                        let skips_recapture = pruning.recapture
+                            && remaining >= RECAPTURE_MIN_REMAINING_DEPTH
+                            && recapture_square.is_some_and(|square| mv.get_dest() != square);
+
+                        let a = if exact { window_alpha } else { *alpha.read() };
+
+                        let search = |alpha: Score, beta: Score, max_depth: u8| {
+                            BoardEvaluation::from_child(
+                                self.evaluate_board(
+                                    &next,
+                                    beta.unflip(),
+                                    alpha.unflip(),
+                                    depth + 1,
+                                    max_depth,
+                                    Some(&child_path),
+                                ),
+                                mv,
+                            )
+                        };
+
+                        // Principal variation search: the first move is most likely the best,
+                        // so later moves are first searched with a null window, only to prove that they don't beat alpha.
+                        // If one does, then it is searched again with the full window to get its real score.
+                        let null_beta = if i > 0 && !exact {
+                            null_window_beta(a)
+                        } else {
+                            None
+                        };
+                        let full_search = |max_depth: u8| match null_beta {
+                            Some(null_beta) => {
+                                let eval = search(a, null_beta, max_depth);
+                                if eval.score > a && eval.score < beta {
+                                    search(a, beta, max_depth)
+                                } else {
+                                    eval
+                                }
+                            }
+                            None => search(a, beta, max_depth),
+                        };
+
+                        let eval = if futile && quiet {
+                            // Assume the move is no better than the position already is
+                            BoardEvaluation {
+                                mv: Some(mv),
+                                depth,
+                                score: static_eval,
+                                terminated_early: false,
+                            }
+                        } else if prunable && (late_move || skips_recapture) {
+                            // Unlikely moves are searched one ply shallower first,
+                            // and only searched fully if they turn out to beat alpha
+                            let reduced = search(a, null_beta.unwrap_or(beta), max_depth - 1);
+
+                            if reduced.score > a {
+                                full_search(max_depth)
+                            } else {
+                                reduced
+                            }
+                        } else {
+                            full_search(max_depth)
+                        };
+
+                        if depth == 0 {
+                            self.root_evals.lock().push(eval);
+                        }
+
+                        if Some(eval) > *best.read() {
+                            {
+                                BoardEvaluation::overwrite_best(&mut best.write(), eval);
+                            }
+                            if depth == 0 {
+                                self.send_event(SearchEvent::BestMoveChanged {
+                                    mv,
+                                    score: eval.score,
+                                    depth: self.current_search_depth,
+                                });
+                            }
+                            if eval.score > *alpha.read() {
+                                let mut alpha = alpha.write();
+                                *alpha = eval.score;
+                                if quiet {
+                                    self.history_table.record(mv, remaining);
+                                }
+                            }
+                        }
+
+                        if depth == 0 {
+                            if let Some(best) = *best.read() {
+                                self.report_progress(best);
+                            }
+                        }
+
+                        if eval.score >= beta && !exact {
+                            if quiet && self.config.pruning.killers {
+                                self.killers.write().store(depth, mv);
+                            }
+                            return *best.read();
+                        }
+
+                        None
+                    };
+
+                    // Young brothers wait: the first (and most likely best) move is searched on its own,
+                    // so that the rest of the moves are searched in parallel with a real bound, rather than the initial window.
+                    // This also means that with a single thread, moves are searched in order, and the search is deterministic.
+                    // With more threads, the siblings race each other, so results can still vary slightly from run to run.
+                    //
+                    // This will always return some value,
+                    // as long as the above list has at least one valid move.
                     // This is always the case, because the cases where no moves are available (mates)
                     // are handled above
-                    (&mut iter)
-                        .par_bridge()
-                        .into_par_iter()
-                        .find_map_any(|mv| {
-                            let next = board.make_move_new(mv);
+                    let first = moves[0];
+                    let eval = search_move((0, first))
+                        .or_else(|| {
+                            moves
+                                .into_par_iter()
+                                .enumerate()
+                                .skip(1)
+                                .find_map_any(search_move)
+                        })
+                        .or(*best.read())
+                        .expect("Searched at least one legal move");
 
-                            let a = { *alpha.read() };
-                            let eval = BoardEvaluation::from_child(
-                                self.evaluate_board(&next, beta.negate(), a.negate(), depth + 1),
-                                mv,
-                            );
+                    // A restricted root's result doesn't hold for the position in general, so it isn't stored
+                    if !eval.terminated_early && !restricted {
+                        let node_type = if eval.score >= beta {
+                            NodeType::Cut
+                        } else if eval.score > window_alpha {
+                            NodeType::Pv
+                        } else {
+                            NodeType::All
+                        };
+
+                        self.transposition_table.write().insert(
+                            hash,
+                            TranspositionData {
+                                best_move: eval.mv,
+                                score: eval.score,
+                                node_type,
+                                depth: remaining,
+                            },
+                        );
+                    }
+
+                    eval
+                }
+            }
+        }
+    }
+
+    /// Whether the search should stop early, either because we ran out of time or nodes, or were told to stop
+    ///
+    /// The time limit doesn't apply while pondering
+    fn should_stop(&self) -> bool {
+        self.stop.load(AtomicOrdering::Relaxed)
+            || self.node_limit.is_some_and(|limit| self.nodes() >= limit)
+            || self.past_time_limit(self.stop_time)
+    }
+
+    /// Whether `limit` has passed, counting from the `ponderhit` if ponder time isn't counted
+    ///
+    /// Time limits don't apply while pondering
+    fn past_time_limit(&self, limit: Option<Instant>) -> bool {
+        if self.pondering.load(AtomicOrdering::Relaxed) {
+            return false;
+        }
+
+        self.note_ponder_hit();
+        let offset = Duration::from_micros(self.ponder_offset_us.load(AtomicOrdering::Relaxed));
+        limit.is_some_and(|limit| Instant::now() > limit + offset)
+    }
+
+    /// Records how long we pondered for, the first time the search notices that it is no longer pondering
+    ///
+    /// The ponder flag is cleared from another thread on `ponderhit`, so this is checked whenever the time limits are.
+    /// It is polled often enough that the time between the `ponderhit` and noticing it doesn't matter.
+    fn note_ponder_hit(&self) {
+        if !self.ponder_pending.load(AtomicOrdering::Acquire) {
+            return;
+        }
+
+        // The offset is stored before the flag is cleared, so that other search threads never see the limits without it.
+        // Several threads may store it at once, but they'd all measure practically the same time
+        let Some(start_time) = self.start_time else {
+            return;
+        };
+        let pondered = start_time.elapsed();
+        self.ponder_offset_us
+            .store(pondered.as_micros() as u64, AtomicOrdering::Relaxed);
+
+        if self.ponder_pending.swap(false, AtomicOrdering::Release) {
+            self.debug_info(|| {
+                format!(
+                    "ponderhit after {}ms, time limits pushed back",
+                    pondered.as_millis()
+                )
+            });
+        }
+    }
+
+    /// The best root move of an interrupted iteration, if it beat the last completed iteration's best move
+    ///
+    /// The previous best move is searched first (see [`Self::evaluate_board`]), so this is only trusted once that has finished.
+    /// Only moves whose search finished count, as the rest were scored by a partial search.
+    fn interrupted_improvement(&mut self) -> Option<BoardEvaluation> {
+        let previous_best = self.best_move_found?;
+        let root_evals = self.root_evals.get_mut();
+        let completed = || root_evals.iter().filter(|eval| !eval.terminated_early);
+
+        let previous_eval = completed().find(|eval| eval.mv == Some(previous_best))?;
+        completed()
+            .filter(|eval| eval.mv != Some(previous_best) && *eval > previous_eval)
+            .max()
+            .copied()
+    }
+
+    /// Pushes the soft time limit back by [`TIME_EXTENSION`] of the current time budget, without going past the hard limit
+    fn extend_soft_stop_time(&mut self) {
+        let (Some(start), Some(soft), Some(hard)) =
+            (self.start_time, self.soft_stop_time, self.stop_time)
+        else {
+            return;
+        };
+
+        let extension = (soft - start) * TIME_EXTENSION.0 / TIME_EXTENSION.1;
+        self.soft_stop_time = Some((soft + extension).min(hard));
+        self.debug_info(|| {
+            format!(
+                "unstable iteration, time budget extended to {}ms",
+                (self.soft_stop_time.unwrap_or(soft) - start).as_millis()
+            )
+        });
+    }
+
+    /// Whether the soft time limit has passed, so that no new iteration should be started
+    ///
+    /// Like the hard limit, this doesn't apply while pondering
+    fn past_soft_stop_time(&self) -> bool {
+        self.past_time_limit(self.soft_stop_time)
+    }
+
+    /// Why [`Self::should_stop`] stopped the search, for debugging
+    fn stop_reason(&self) -> &'static str {
+        if self.stop.load(AtomicOrdering::Relaxed) {
+            "stop command"
+        } else if self.node_limit.is_some_and(|limit| self.nodes() >= limit) {
+            "node limit"
+        } else {
+            "hard time limit"
+        }
+    }
+
+    /// Blocks until we are no longer pondering, or the search is stopped
+    ///
+    /// The best move can't be sent while pondering, even if the search has nothing left to do.
+    /// If we had to wait, the result is reported again after the wait, right before the best move,
+    /// so that what was found while pondering (such as a mate against us) isn't lost among the `info` sent on the opponent's time.
+    fn wait_while_pondering(&self) {
+        let mut waited = false;
+        while self.pondering.load(AtomicOrdering::Relaxed)
+            && !self.stop.load(AtomicOrdering::Relaxed)
+        {
+            waited = true;
+            thread::sleep(PONDER_POLL_INTERVAL);
+        }
+
+        if let (true, Some(mv), Some(score)) = (waited, self.best_move_found, self.best_score_found)
+        {
+            let search_time_ms = self
+                .start_time
+                .map(|start_time| start_time.elapsed().as_millis())
+                .unwrap_or_default();
+            uci_log::send(UciResponse::info(
+                UciInfo::new()
+                    .score(UciScore::from(score))
+                    .pv(self.principal_variation(mv))
+                    .depth(self.completed_depth)
+                    .nodes(self.nodes())
+                    .time(search_time_ms),
+            ));
+        }
+    }
+
+    /// Prints the best root move found so far in the current iteration,
+    /// if [`EngineConfig::progress_interval`] has passed since the last report
+    fn report_progress(&self, best: BoardEvaluation) {
+        let (Some(interval), Some(start_time), Some(mv)) =
+            (self.config.progress_interval, self.start_time, best.mv)
+        else {
+            return;
+        };
+
+        let now = Instant::now();
+        {
+            let mut last_report = self.last_report.lock();
+            if now - last_report.unwrap_or(start_time) < interval {
+                return;
+            }
+            *last_report = Some(now);
+        }
+
+        uci_log::send(UciResponse::info(
+            UciInfo::new()
+                .score(UciScore::from(best.score))
+                .pv([mv.to_string()])
+                .depth(self.current_search_depth)
+                .nodes(self.nodes())
+                .time((now - start_time).as_millis()),
+        ));
+    }
+
+    /// The expected line of play starting with the root move `first`, as far as it can be recovered
+    ///
+    /// This follows the best moves stored for exact (PV) nodes in the transposition table,
+    /// until there isn't one, the line repeats, or it reaches the current search depth.
+    /// The depth cap also guards against following a hash collision around in circles.
+    fn principal_variation(&self, first: ChessMove) -> Vec<String> {
+        let transposition_table = self.transposition_table.read();
+        let mut board = self.board.make_move_new(first);
+        let mut seen = vec![self.config.hash_scheme.hash(&self.board)];
+        let mut pv = vec![first];
+
+        while pv.len() < self.current_search_depth as usize {
+            let hash = self.config.hash_scheme.hash(&board);
+            if seen.contains(&hash) {
+                break;
+            }
+            seen.push(hash);
+
+            let Some(mv) = transposition_table
+                .get(hash)
+                .filter(|t| t.node_type == NodeType::Pv)
+                .and_then(|t| t.best_move)
+                .filter(|mv| board.legal(*mv))
+            else {
+                break;
+            };
+
+            board = board.make_move_new(mv);
+            pv.push(mv);
+        }
+
+        let mut board = self.board;
+        pv.into_iter()
+            .map(|mv| {
+                let uci = self.uci_move(&board, mv);
+                board = board.make_move_new(mv);
+                uci
+            })
+            .collect()
+    }
+
+    /// Prints the root move that is about to be searched, and its place in the move order (starting at 1),
+    /// once the search has been running for [`CURRMOVE_DELAY`]
+    fn report_current_move(&self, mv: ChessMove, number: usize) {
+        if self
+            .start_time
+            .is_none_or(|start_time| start_time.elapsed() < CURRMOVE_DELAY)
+        {
+            return;
+        }
 
-                            if eval > *best.read() {
-                                {
-                                    best.write().overwrite(eval);
-                                }
-                                if eval.score > *alpha.read() {
-                                    let mut alpha = alpha.write();
-                                    *alpha = eval.score;
-                                }
-                            }
+        uci_log::send(UciResponse::info(
+            UciInfo::new()
+                .depth(self.current_search_depth)
+                .currmove(self.uci_move(&self.board, mv))
+                .currmovenumber(number),
+        ));
+    }
 
-                            if eval.score >= beta {
-                                let best = { *best.read() };
-                                return Some(best);
-                            }
+    /// Razoring: when the static evaluation is far below alpha close to the frontier,
+    /// verify with a quiescence search that nothing tactical is going on, and if so, skip the full search
+    ///
+    /// The quiescence search only needs to tell whether we are still below alpha, so it uses a null window.
+    ///
+    /// Returns the evaluation to use for this node if it was pruned
+    fn razor(
+        &self,
+        board: &Board,
+        static_eval: Score,
+        alpha: Score,
+        depth: u8,
+        remaining: u8,
+        halfmove_clock: u32,
+    ) -> Option<BoardEvaluation> {
+        let Score::Centipawns(alpha_cp) = alpha else {
+            return None;
+        };
+        if remaining > RAZORING_MAX_REMAINING_DEPTH
+            || !below_with_margin(static_eval, RAZORING_MARGIN * remaining as i16, alpha)
+        {
+            return None;
+        }
 
-                            None
-                        })
-                        .unwrap_or(*best.read())
-                }
-            }
+        let eval = self.evaluate_board_quiescence(
+            board,
+            Score::cp(alpha_cp.saturating_sub(1)),
+            alpha,
+            depth,
+            halfmove_clock,
+        );
+        (eval.score < alpha && !eval.terminated_early).then_some(eval)
+    }
+
+    /// Null move pruning: if passing our turn still leaves us at or above beta after a reduced search,
+    /// then a real move almost certainly would too, so this node can be cut off
+    ///
+    /// This is skipped when we only have pawns left, as zugzwang is common in those positions.
+    /// Returns the evaluation to use for this node if it was pruned
+    fn null_move_prune(
+        &self,
+        board: &Board,
+        static_eval: Score,
+        beta: Score,
+        depth: u8,
+        max_depth: u8,
+        path: &SearchPath,
+    ) -> Option<BoardEvaluation> {
+        let Score::Centipawns(beta_cp) = beta else {
+            return None;
+        };
+        if max_depth - depth < NULL_MOVE_MIN_REMAINING_DEPTH
+            || !at_least(static_eval, beta)
+            || !has_non_pawn_material(board)
+        {
+            return None;
         }
+
+        let next = board.null_move()?;
+        let child = self.evaluate_board(
+            &next,
+            beta.unflip(),
+            Score::cp(beta_cp.saturating_sub(1)).unflip(),
+            depth + 1,
+            max_depth - NULL_MOVE_REDUCTION,
+            Some(path),
+        );
+
+        // Mate scores from a null move search can't be trusted, as passing isn't a legal move
+        (!child.terminated_early && at_least(child.score.flip(), beta))
+            .then(|| BoardEvaluation::score(beta, child.depth))
     }
 
     /// Evaluates all quiet positions on the provided board, assuming we are up to move
     ///
-    /// Only quiet positions (captures) are evaluated
+    /// Only moves that change the material balance are searched (see [`quiescence_moves`]),
+    /// unless we are in check, in which case every evasion is searched
     fn evaluate_board_quiescence(
         &self,
         board: &Board,
         alpha: Score,
         beta: Score,
         depth: u8,
+        halfmove_clock: u32,
     ) -> BoardEvaluation {
+        self.nodes.fetch_add(1, AtomicOrdering::Relaxed);
+
         match board.status() {
             BoardStatus::Checkmate => {
                 // We lost :(
                 BoardEvaluation::score(Score::Mate(0), depth)
             }
-            BoardStatus::Stalemate => BoardEvaluation::score(Score::cp(0), depth),
+            BoardStatus::Stalemate => BoardEvaluation::score(self.draw_score(board, depth), depth),
+            // Captures often leave too little material to mate with, which no amount of searching will change
+            BoardStatus::Ongoing if depth > 0 && is_insufficient_material(board) => {
+                BoardEvaluation::score(self.draw_score(board, depth), depth)
+            }
             BoardStatus::Ongoing => {
-                if self
-                    .stop_time
-                    .map(|st| Instant::now() > st)
-                    .unwrap_or_default()
-                {
-                    // Early termination on time
+                if self.should_stop() {
+                    // Early termination on time, or on the stop command
                     // Hueristic based on material
-                    BoardEvaluation::score_early(eval_heuristic(board), depth)
+                    BoardEvaluation::score_early(
+                        scaled_eval(board, halfmove_clock, &self.config.eval_params),
+                        depth,
+                    )
                 } else {
                     // Down the tree we go
-                    let mut iter = MoveGen::new_legal(board);
-                    iter.remove_mask(!board.color_combined(!board.side_to_move()));
+                    let moves = quiescence_moves(board);
 
-                    let stand_pat = eval_heuristic(board);
-                    if stand_pat >= beta {
-                        return BoardEvaluation::score(stand_pat, depth);
-                    }
+                    // When in check, we can't choose to do nothing, so there is no stand pat
+                    let in_check = *board.checkers() != EMPTY;
+                    let stand_pat = (!in_check)
+                        .then(|| scaled_eval(board, halfmove_clock, &self.config.eval_params));
+                    let best = if let Some(stand_pat) = stand_pat {
+                        if stand_pat >= beta {
+                            return BoardEvaluation::score(stand_pat, depth);
+                        }
 
-                    let alpha = if alpha < stand_pat {
-                        RwLock::new(stand_pat)
+                        Some(BoardEvaluation::score(stand_pat, depth))
                     } else {
-                        RwLock::new(alpha)
+                        None
                     };
-                    let best = RwLock::new(BoardEvaluation::score(stand_pat, depth));
 
-                    (&mut iter)
-                        .par_bridge()
+                    let delta_pruning =
+                        self.config.pruning.delta && game_phase(board) >= DELTA_PRUNING_MIN_PHASE;
+
+                    let alpha = RwLock::new(best.map_or(alpha, |best| alpha.max(best.score)));
+                    let best = RwLock::new(best);
+
+                    moves
                         .into_par_iter()
                         .find_map_any(|mv| {
-                            let next = board.make_move_new(mv);
-
                             let a = { *alpha.read() };
+
+                            // Delta pruning: skip captures that can't raise alpha, even if they win the piece for free
+                            let hopeless = delta_pruning
+                                && mv.get_promotion().is_none()
+                                && stand_pat.zip(captured_piece(board, mv)).is_some_and(
+                                    |(stand_pat, victim)| {
+                                        below_with_margin(
+                                            stand_pat,
+                                            piece_value(victim) + DELTA_MARGIN,
+                                            a,
+                                        )
+                                    },
+                                );
+                            // Captures that lose material once all the recaptures are played out are never worth it either
+                            let losing = self.config.pruning.see
+                                && !in_check
+                                && mv.get_promotion().is_none()
+                                && see(board, mv) < 0;
+                            if hopeless || losing {
+                                return None;
+                            }
+
+                            let next = board.make_move_new(mv);
                             let eval = BoardEvaluation::from_child(
                                 self.evaluate_board_quiescence(
                                     &next,
-                                    beta.negate(),
-                                    a.negate(),
+                                    beta.unflip(),
+                                    a.unflip(),
                                     depth + 1,
+                                    halfmove_clock_after(board, mv, halfmove_clock),
                                 ),
                                 mv,
                             );
@@ -353,8 +2150,8 @@ impl Engine {
                             if eval.score >= beta {
                                 return Some(eval);
                             }
-                            if eval > *best.read() {
-                                best.write().overwrite(eval);
+                            if Some(eval) > *best.read() {
+                                BoardEvaluation::overwrite_best(&mut best.write(), eval);
                             }
                             if eval.score > *alpha.read() {
                                 let mut alpha = alpha.write();
@@ -363,13 +2160,123 @@ impl Engine {
 
                             None
                         })
-                        .unwrap_or(*best.read())
+                        .or(*best.read())
+                        .expect("Either stood pat or searched at least one legal move")
                 }
             }
         }
     }
 }
 
+/// The halfmove clock and fullmove number of a position, as found in its FEN
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct MoveCounters {
+    /// Number of plies since the last capture or pawn move
+    halfmove_clock: u32,
+    /// Number of the current move, starting at 1 and incremented after each of black's moves
+    fullmove_number: u32,
+}
+
+impl Default for MoveCounters {
+    fn default() -> Self {
+        Self {
+            halfmove_clock: 0,
+            fullmove_number: 1,
+        }
+    }
+}
+
+impl MoveCounters {
+    /// Parses the move counters from the last two fields of `fen`
+    ///
+    /// These fields are optional, so missing counters take their default values
+    fn from_fen(fen: &str) -> anyhow::Result<Self> {
+        let mut fields = fen.split_whitespace().skip(4);
+        let default = Self::default();
+
+        Ok(Self {
+            halfmove_clock: fields
+                .next()
+                .map(|f| f.parse::<u32>().context("Invalid halfmove clock in FEN"))
+                .transpose()?
+                .unwrap_or(default.halfmove_clock),
+            fullmove_number: fields
+                .next()
+                .map(|f| f.parse::<u32>().context("Invalid fullmove number in FEN"))
+                .transpose()?
+                .unwrap_or(default.fullmove_number),
+        })
+    }
+
+    /// Updates the counters for `mv` being made on `board`
+    ///
+    /// Must be called *before* the move is made, as we need to know what is being moved and captured
+    fn update(&mut self, board: &Board, mv: ChessMove) {
+        self.halfmove_clock = halfmove_clock_after(board, mv, self.halfmove_clock);
+
+        if board.side_to_move() == Color::Black {
+            self.fullmove_number += 1;
+        }
+    }
+}
+
+/// The positions on the path from the root of the search to the current node, used to detect repetitions
+///
+/// Each node of the search borrows its parent's path, so this is a linked list living on the stack
+#[derive(Debug, Clone, Copy)]
+struct SearchPath<'a> {
+    /// Hash of the position
+    hash: u64,
+    /// Whether the move out of this position (towards the current node) was a null move
+    null_move: bool,
+    /// The square that the move out of this position captured on, if it was a capture
+    captured_on: Option<Square>,
+    /// The halfmove clock after the move out of this position
+    halfmove_clock_after: u32,
+    /// The path to the parent position, if this position isn't the root
+    parent: Option<&'a SearchPath<'a>>,
+}
+
+impl<'a> SearchPath<'a> {
+    /// Extends `parent` with the position with `hash`,
+    /// which is being left by a null move if `null_move` is set, or by a capture on `captured_on`,
+    /// leaving the halfmove clock at `halfmove_clock_after`
+    fn new(
+        hash: u64,
+        parent: Option<&'a SearchPath<'a>>,
+        null_move: bool,
+        captured_on: Option<Square>,
+        halfmove_clock_after: u32,
+    ) -> Self {
+        Self {
+            hash,
+            null_move,
+            captured_on,
+            halfmove_clock_after,
+            parent,
+        }
+    }
+
+    /// Whether the position with `hash` already occurred on this path
+    ///
+    /// Positions from before a null move (including the one that was passed from) don't count,
+    /// as passing isn't a legal move
+    fn repeats(&self, hash: u64) -> bool {
+        let mut node = Some(self);
+        while let Some(current) = node {
+            if current.null_move {
+                return false;
+            }
+            if current.hash == hash {
+                return true;
+            }
+            node = current.parent;
+        }
+
+        false
+    }
+}
+
 /// Return value of [`Engine::evaluate_board`]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct BoardEvaluation {
@@ -423,18 +2330,6 @@ impl BoardEvaluation {
         }
     }
 
-    /// Constructs a [`Self`] which is always worse (lower than) than every other [`Self`]
-    ///
-    /// This is used as an identiy value when computing the best of a set of evaluations
-    fn min() -> Self {
-        Self {
-            mv: None,
-            depth: 0,
-            score: Score::Mate(0),
-            terminated_early: false,
-        }
-    }
-
     /// Overwrites the values of `self` with values of `other`, except for depth, which takes the max
     fn overwrite(&mut self, other: Self) {
         self.mv = other.mv;
@@ -442,11 +2337,29 @@ impl BoardEvaluation {
         self.terminated_early = other.terminated_early;
         self.depth = self.depth.max(other.depth);
     }
+
+    /// Overwrites `best` with `other` (see [`Self::overwrite`]), or sets it if there is no best yet
+    ///
+    /// `None` is used as the identity value when computing the best of a set of evaluations,
+    /// as it is lower than every [`Self`], even an actual checkmate
+    fn overwrite_best(best: &mut Option<Self>, other: Self) {
+        match best {
+            Some(best) => best.overwrite(other),
+            None => *best = Some(other),
+        }
+    }
 }
 
 impl Ord for BoardEvaluation {
+    /// Evaluations are ordered by their scores first
+    ///
+    /// [`Score`]'s ordering already prefers faster mates (and slower losses).
+    /// When scores are tied, the evaluation that was searched deeper is preferred,
+    /// as its score is more trustworthy.
     fn cmp(&self, other: &Self) -> Ordering {
-        self.score.cmp(&other.score)
+        self.score
+            .cmp(&other.score)
+            .then_with(|| self.depth.cmp(&other.depth))
     }
 }
 
@@ -455,3 +2368,749 @@ impl PartialOrd for BoardEvaluation {
         Some(self.cmp(other))
     }
 }
+
+/// Uses a stored result for a position, if it was searched at least `remaining` plies deep
+///
+/// Exact scores are always used, while bounds are only used if they fall outside of the search window
+fn probe_transposition(
+    transposition: Option<TranspositionData>,
+    alpha: Score,
+    beta: Score,
+    depth: u8,
+    remaining: u8,
+) -> Option<BoardEvaluation> {
+    let transposition = transposition.filter(|t| t.depth >= remaining)?;
+
+    let usable = match transposition.node_type {
+        NodeType::Pv => true,
+        NodeType::All => transposition.score <= alpha,
+        NodeType::Cut => transposition.score >= beta,
+    };
+
+    usable.then_some(BoardEvaluation {
+        mv: transposition.best_move,
+        depth,
+        score: transposition.score,
+        terminated_early: false,
+    })
+}
+
+/// The halfmove clock after `mv` is made on `board`, where the clock was at `halfmove_clock`
+///
+/// The clock is reset by pawn moves and captures, and counts up otherwise
+fn halfmove_clock_after(board: &Board, mv: ChessMove, halfmove_clock: u32) -> u32 {
+    let pawn_move = board.piece_on(mv.get_source()) == Some(Piece::Pawn);
+    let capture = board.piece_on(mv.get_dest()).is_some();
+
+    if pawn_move || capture {
+        0
+    } else {
+        halfmove_clock + 1
+    }
+}
+
+/// Static evaluation of `board` with the weights in `params`, where the halfmove clock is at `halfmove_clock`
+///
+/// The evaluation is pulled towards a draw as the clock approaches the fifty-move rule,
+/// so that shuffling pieces around without making progress looks worse than it is
+fn scaled_eval(board: &Board, halfmove_clock: u32, params: &EvalParams) -> Score {
+    match eval_heuristic(board, params) {
+        Score::Centipawns(cp) => {
+            let remaining = FIFTY_MOVE_LIMIT.saturating_sub(halfmove_clock) as i32;
+            let scaled =
+                cp as i32 * (FIFTY_MOVE_LIMIT as i32 + remaining) / (2 * FIFTY_MOVE_LIMIT as i32);
+            Score::cp(scaled as i16)
+        }
+        score => score,
+    }
+}
+
+/// Moves searched by [`Engine::evaluate_board_quiescence`]
+///
+/// This is all captures (including en passant) and promotions,
+/// or every legal move if the player up to move is in check
+fn quiescence_moves(board: &Board) -> Vec<ChessMove> {
+    let in_check = *board.checkers() != EMPTY;
+
+    MoveGen::new_legal(board)
+        .filter(|mv| in_check || mv.get_promotion().is_some() || is_capture(board, *mv))
+        .collect()
+}
+
+/// Whether `mv` captures a piece on `board`
+///
+/// En passant captures are the only captures that land on an empty square,
+/// so they are detected separately as pawns moving diagonally
+fn is_capture(board: &Board, mv: ChessMove) -> bool {
+    board.piece_on(mv.get_dest()).is_some()
+        || (board.piece_on(mv.get_source()) == Some(Piece::Pawn)
+            && mv.get_source().get_file() != mv.get_dest().get_file())
+}
+
+/// Whether `score` is still below `bound` after adding `margin` centipawns
+///
+/// Margins are meaningless for mate scores, so this is always false if either score is a mate
+fn below_with_margin(score: Score, margin: i16, bound: Score) -> bool {
+    match (score, bound) {
+        (Score::Centipawns(score), Score::Centipawns(bound)) => {
+            score.saturating_add(margin) < bound
+        }
+        _ => false,
+    }
+}
+
+/// Upper bound of the null window just above `alpha`, as used by principal variation search
+///
+/// There is no score directly above a mate score, so this is `None` if `alpha` is a mate
+fn null_window_beta(alpha: Score) -> Option<Score> {
+    match alpha {
+        Score::Centipawns(cp) => cp.checked_add(1).map(Score::cp),
+        Score::Mate(_) => None,
+    }
+}
+
+/// Whether `score` is at least `bound`, considering only centipawn scores
+///
+/// This is always false if either score is a mate
+fn at_least(score: Score, bound: Score) -> bool {
+    matches!((score, bound), (Score::Centipawns(score), Score::Centipawns(bound)) if score >= bound)
+}
+
+/// Whether the player up to move has any pieces other than pawns and their king
+fn has_non_pawn_material(board: &Board) -> bool {
+    let pawns_and_kings = *board.pieces(Piece::Pawn) | *board.pieces(Piece::King);
+    *board.color_combined(board.side_to_move()) & !pawns_and_kings != EMPTY
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Searches to `depth` without a time limit, so that results don't depend on the speed of the machine
+    fn to_depth(depth: u32) -> UciSearchOptions {
+        UciSearchOptions {
+            depth: Some(depth),
+            infinite: true,
+            ..Default::default()
+        }
+    }
+
+    /// A default engine set up on `fen`
+    fn engine_at(fen: &str) -> Engine {
+        let mut engine = Engine::default();
+        engine.set_position(Some(fen), std::iter::empty()).unwrap();
+        engine
+    }
+
+    /// Score of the last of `moves` from `fen` (the starting position if `None`), which repeats a position for the third time,
+    /// with the given `contempt`
+    fn repetition_score(
+        engine: &mut Engine,
+        fen: Option<&str>,
+        moves: &[&str],
+        contempt: i16,
+    ) -> Score {
+        let mut moves: Vec<_> = moves
+            .iter()
+            .map(|mv| ChessMove::from_str(mv).unwrap())
+            .collect();
+        let repeating = moves.pop().unwrap();
+        engine.set_contempt(contempt);
+        engine.set_position(fen, moves.into_iter()).unwrap();
+
+        let ranked = engine.rank_moves(to_depth(2)).unwrap();
+        ranked
+            .into_iter()
+            .find(|(mv, _)| *mv == repeating)
+            .map(|(_, score)| score)
+            .unwrap()
+    }
+
+    /// Nodes searched to depth 5 from a quiet middlegame, on a single thread so that the count is deterministic
+    fn nodes_with(pruning: PruningConfig) -> u64 {
+        let mut engine = Engine::with_config(EngineConfig {
+            threads: 1,
+            pruning,
+            ..Default::default()
+        });
+        let fen = "r2q1rk1/pp2bppp/2n1bn2/3p4/3P4/2NBBN2/PP3PPP/R2Q1RK1 w - - 0 1";
+        engine.set_position(Some(fen), std::iter::empty()).unwrap();
+        engine.analyze(to_depth(5)).unwrap().nodes
+    }
+
+    #[test]
+    fn nodes_are_counted_per_search() {
+        let mut engine = Engine::default();
+        engine.analyze(to_depth(4)).unwrap();
+        let deep = engine.nodes();
+
+        engine.analyze(to_depth(1)).unwrap();
+        let shallow = engine.nodes();
+        assert!(shallow < deep, "{} >= {}", shallow, deep);
+    }
+
+    #[test]
+    fn quiescence_resolves_check_before_standing_pat() {
+        // White is a queen up, but in check from the knight, which forks the king and queen
+        let fen = "4k3/7p/8/8/8/8/2n5/Q3K3 w - - 0 1";
+        let mut engine = Engine::default();
+        engine.set_position(Some(fen), std::iter::empty()).unwrap();
+
+        let score = engine
+            .quiescence_eval(&UciSearchOptions::default())
+            .unwrap();
+        assert!(score < Score::cp(0), "got {:?}", score);
+    }
+
+    #[test]
+    fn fastest_mate_is_chosen() {
+        // 1. Qg7# mates right away, while plenty of other queen moves mate a move later
+        let mut engine = engine_at("7k/8/5K2/8/8/8/8/6Q1 w - - 0 1");
+        let ranked = engine.rank_moves(to_depth(4)).unwrap();
+        assert!(
+            ranked.iter().any(|(_, score)| *score == Score::mate(3)),
+            "{:?}",
+            ranked
+        );
+
+        let result = engine.analyze(to_depth(4)).unwrap();
+        assert_eq!(result.score, Score::mate(1));
+        assert_eq!(result.best_move, ChessMove::from_str("g1g7").unwrap());
+    }
+
+    #[test]
+    fn contempt_fades_in_the_endgame() {
+        // The kings shuffle back and forth, with a rook each (a sixth of the starting position's phase)
+        let score = repetition_score(
+            &mut Engine::default(),
+            Some("4k3/4r3/8/8/8/8/4R3/4K3 w - - 0 1"),
+            &[
+                "e1d1", "e8d8", "d1e1", "d8e8", "e1d1", "e8d8", "d1e1", "d8e8",
+            ],
+            48,
+        );
+        assert_eq!(score, Score::cp(-48 / 6));
+    }
+
+    #[test]
+    fn movestogo_keeps_time_for_the_last_move() {
+        let clock = Duration::from_secs(60);
+        let allocate = |movestogo| {
+            allocate_time(
+                &UciSearchOptions {
+                    wtime: Some(clock),
+                    movestogo: Some(movestogo),
+                    ..Default::default()
+                },
+                &Board::default(),
+            )
+            .unwrap()
+        };
+
+        // The first move of a 40-move control gets around a fortieth of the clock
+        let (first, _) = allocate(40);
+        assert!(first > clock / 80 && first < clock / 20, "{:?}", first);
+
+        // The last move gets more, but never spends the whole clock
+        let (last, last_hard) = allocate(1);
+        assert!(last > first, "{:?} <= {:?}", last, first);
+        assert!(last_hard <= clock / 2, "{:?}", last_hard);
+    }
+
+    #[test]
+    fn movestogo_shares_the_time_until_the_control() {
+        let time = Duration::from_secs(40);
+        // An even share, plus a quarter
+        assert_eq!(
+            movestogo_thinking_time(time, Duration::ZERO, 40),
+            Duration::from_millis(1250)
+        );
+        // The increments received before the control count towards it
+        assert_eq!(
+            movestogo_thinking_time(time, Duration::from_secs(1), 40),
+            (Duration::from_secs(40 + 39) / 40) * 5 / 4
+        );
+        // A tenth of the clock is always kept in reserve, even for the last move
+        assert_eq!(
+            movestogo_thinking_time(time, Duration::ZERO, 1),
+            Duration::from_secs(36)
+        );
+        assert_eq!(
+            movestogo_thinking_time(time, Duration::ZERO, 0),
+            movestogo_thinking_time(time, Duration::ZERO, 1)
+        );
+    }
+
+    #[test]
+    fn every_legal_move_is_ranked_once_best_first() {
+        let mut engine = Engine::default();
+        let ranked = engine.rank_moves(to_depth(3)).unwrap();
+
+        // With as many moves as there are legal moves, containing each legal move means containing each exactly once
+        let legal: Vec<_> = MoveGen::new_legal(engine.board()).collect();
+        assert_eq!(ranked.len(), legal.len());
+        assert!(
+            legal
+                .iter()
+                .all(|mv| ranked.iter().any(|(ranked, _)| ranked == mv)),
+            "{:?}",
+            ranked
+        );
+        assert!(
+            ranked.windows(2).all(|pair| pair[0].1 >= pair[1].1),
+            "{:?}",
+            ranked
+        );
+    }
+
+    #[test]
+    fn configured_engine_matches_setters() {
+        let config = EngineConfig {
+            hash_mb: 4,
+            threads: 1,
+            ..Default::default()
+        };
+        let mut configured = Engine::with_config(config.clone());
+
+        let mut set = Engine::default();
+        set.set_hash_size(4).unwrap();
+        set.set_threads(1);
+        assert_eq!(*configured.config(), config);
+        assert_eq!(*set.config(), config);
+
+        // The table is only allocated once something is stored in it, so both search first
+        for engine in [&mut configured, &mut set] {
+            engine.search(to_depth(3)).unwrap();
+        }
+        assert_eq!(configured.memory_usage(), set.memory_usage());
+    }
+
+    #[test]
+    fn move_counters_follow_the_moves() {
+        let fen = "4k3/8/8/8/8/8/4P3/4K1N1 w - - 5 10";
+        // The halfmove clock counts up until a pawn move resets it, and the fullmove number counts up after each black move
+        let positions: [(&[&str], &str); 3] = [
+            (&["g1f3"], "6 10"),
+            (&["g1f3", "e8d8"], "7 11"),
+            (&["g1f3", "e8d8", "e2e4"], "0 11"),
+        ];
+
+        let mut engine = Engine::default();
+        for (moves, expected) in positions {
+            let moves = moves.iter().map(|mv| ChessMove::from_str(mv).unwrap());
+            engine.set_position(Some(fen), moves).unwrap();
+
+            let fen = engine.current_fen();
+            let counters: Vec<_> = fen.split_whitespace().skip(4).collect();
+            assert_eq!(counters.join(" "), expected, "{}", fen);
+        }
+    }
+
+    #[test]
+    fn new_eval_params_clear_the_table() {
+        // A small table, so that a short search fills a noticeable part of it
+        let mut engine = Engine::with_config(EngineConfig {
+            hash_mb: 1,
+            ..Default::default()
+        });
+        engine.search(to_depth(5)).unwrap();
+        assert!(engine.hashfull() > 0);
+
+        engine.set_eval_params(EvalParams::MATERIAL_ONLY);
+        assert_eq!(engine.hashfull(), 0);
+    }
+
+    #[test]
+    fn killer_moves_change_the_search() {
+        let with_killers = nodes_with(PruningConfig::default());
+        let without_killers = nodes_with(PruningConfig {
+            killers: false,
+            ..Default::default()
+        });
+        assert_ne!(with_killers, without_killers);
+    }
+
+    #[test]
+    fn null_window_is_one_centipawn_wide() {
+        assert_eq!(null_window_beta(Score::cp(10)), Some(Score::cp(11)));
+        assert_eq!(null_window_beta(Score::cp(-1)), Some(Score::cp(0)));
+        // Without room above alpha, or with a mate score, the move has to be searched with the full window
+        assert_eq!(null_window_beta(Score::cp(i16::MAX)), None);
+        assert_eq!(null_window_beta(Score::mate(3)), None);
+    }
+
+    #[test]
+    fn mates_beyond_the_aspiration_window_are_scored_exactly() {
+        // 1. Re8+ Rxe8 2. Rxe8# only shows up once the search gets deep enough, after it has set a centipawn window
+        let mut engine = engine_at("2r3k1/5ppp/8/8/8/8/4RPPP/4R1K1 w - - 0 1");
+        let result = engine.analyze(to_depth(5)).unwrap();
+        assert_eq!(result.score, Score::mate(3));
+        assert_eq!(result.best_move, ChessMove::from_str("e2e8").unwrap());
+    }
+
+    #[test]
+    fn parallel_search_finds_the_same_mate() {
+        for threads in [1, 4] {
+            let mut engine = Engine::with_config(EngineConfig {
+                threads,
+                ..Default::default()
+            });
+            let fen = "2r3k1/5ppp/8/8/8/8/4RPPP/4R1K1 w - - 0 1";
+            engine.set_position(Some(fen), std::iter::empty()).unwrap();
+
+            let result = engine.analyze(to_depth(4)).unwrap();
+            assert_eq!(result.score, Score::mate(3), "{} threads", threads);
+            assert_eq!(result.best_move, ChessMove::from_str("e2e8").unwrap());
+        }
+    }
+
+    #[test]
+    fn single_threaded_search_is_deterministic() {
+        let first = nodes_with(PruningConfig::default());
+        let second = nodes_with(PruningConfig::default());
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn continuing_a_position_matches_setting_it_up_from_scratch() {
+        let moves = ["e2e4", "g8f6", "g1f3", "f6g8", "f3g1", "e7e5"]
+            .map(|mv| ChessMove::from_str(mv).unwrap());
+
+        let mut incremental = Engine::default();
+        for played in 1..=moves.len() {
+            incremental
+                .set_position(None, moves[..played].iter().copied())
+                .unwrap();
+        }
+        let mut scratch = Engine::default();
+        scratch.set_position(None, moves.into_iter()).unwrap();
+
+        assert_eq!(incremental.board, scratch.board);
+        assert_eq!(incremental.counters, scratch.counters);
+        assert_eq!(incremental.history, scratch.history);
+    }
+
+    #[test]
+    fn illegal_moves_are_rejected_without_changing_the_position() {
+        let mut engine = Engine::default();
+        let legal = ChessMove::from_str("e2e4").unwrap();
+        engine.set_position(None, std::iter::once(legal)).unwrap();
+        let before = engine.current_fen();
+
+        // The pawn on e2 has already moved
+        let illegal = ChessMove::from_str("e2e4").unwrap();
+        let result = engine.set_position(None, [legal, illegal].into_iter());
+        assert!(result.is_err());
+        assert_eq!(engine.current_fen(), before);
+    }
+
+    #[test]
+    fn go_nodes_stops_the_search_near_the_limit() {
+        let mut engine = Engine::with_config(EngineConfig {
+            threads: 1,
+            ..Default::default()
+        });
+        let mv = engine
+            .search(UciSearchOptions {
+                nodes: Some(5000),
+                ..Default::default()
+            })
+            .unwrap();
+
+        assert!(engine.board().legal(mv));
+        // Once the limit is reached, the remaining siblings along the current line each count one more node as they return
+        let nodes = engine.nodes();
+        assert!((5000..6000).contains(&nodes), "{} nodes", nodes);
+    }
+
+    #[test]
+    fn searchmoves_restricts_the_root_moves() {
+        // Re8+ mates in two, but it isn't one of the moves to search
+        let mut engine = engine_at("2r3k1/5ppp/8/8/8/8/4RPPP/4R1K1 w - - 0 1");
+        let searchmoves = vec!["h2h3".to_string(), "g2g3".to_string()];
+        let mv = engine
+            .search(UciSearchOptions {
+                searchmoves: searchmoves.clone(),
+                ..to_depth(3)
+            })
+            .unwrap();
+        assert!(searchmoves.contains(&mv.to_string()), "{}", mv);
+
+        let illegal = engine.search(UciSearchOptions {
+            searchmoves: vec!["e2e4".to_string()],
+            ..to_depth(3)
+        });
+        assert!(illegal.is_err());
+    }
+
+    #[test]
+    fn hashfull_grows_with_the_search_and_stays_in_permille() {
+        let mut engine = Engine::with_config(EngineConfig {
+            hash_mb: 1,
+            ..Default::default()
+        });
+        assert_eq!(engine.hashfull(), 0);
+
+        engine.analyze(to_depth(4)).unwrap();
+        let shallow = engine.hashfull();
+        engine.analyze(to_depth(6)).unwrap();
+        let deep = engine.hashfull();
+        assert!(0 < shallow && shallow <= deep, "{} then {}", shallow, deep);
+        assert!(deep <= 1000, "{}", deep);
+    }
+
+    #[test]
+    fn delta_pruning_changes_the_search() {
+        let pruned = nodes_with(PruningConfig::default());
+        let unpruned = nodes_with(PruningConfig {
+            delta: false,
+            ..Default::default()
+        });
+        assert!(pruned < unpruned, "{} >= {}", pruned, unpruned);
+    }
+
+    #[test]
+    fn increment_and_movestogo_both_change_the_allocation() {
+        let clock = Some(Duration::from_secs(60));
+        let inc = Some(Duration::from_secs(1));
+        let allocate = |options: UciSearchOptions| allocate_time(&options, &Board::default());
+
+        // A movetime is used as is, rather than allocated from a clock
+        let movetime = allocate(UciSearchOptions {
+            movetime: Some(Duration::from_secs(1)),
+            ..Default::default()
+        });
+        assert_eq!(movetime, None);
+
+        let soft = |winc, movestogo| {
+            allocate(UciSearchOptions {
+                wtime: clock,
+                winc,
+                movestogo,
+                ..Default::default()
+            })
+            .map(|(soft, _)| soft)
+            .unwrap()
+        };
+        let sudden_death = soft(None, None);
+        let with_inc = soft(inc, None);
+        let with_movestogo = soft(None, Some(40));
+        let with_both = soft(inc, Some(40));
+
+        assert!(
+            sudden_death < with_inc,
+            "{:?} >= {:?}",
+            sudden_death,
+            with_inc
+        );
+        assert!(
+            with_movestogo < with_both,
+            "{:?} >= {:?}",
+            with_movestogo,
+            with_both
+        );
+        assert_ne!(with_movestogo, sudden_death);
+        assert_ne!(with_both, with_inc);
+    }
+
+    #[test]
+    fn contempt_applies_to_whoever_is_at_the_root() {
+        // The knights move back and forth, until the last move repeats a position for the third time
+        let shuffle = [
+            "g1f3", "g8f6", "f3g1", "f6g8", "g1f3", "g8f6", "f3g1", "f6g8", "g1f3",
+        ];
+        // One engine searches for both sides, so any draws left in its table from the other side would show up here
+        let mut engine = Engine::default();
+
+        for contempt in [50, -50] {
+            // White repeats, then black repeats
+            for moves in [&shuffle[..], &shuffle[..8]] {
+                let score = repetition_score(&mut engine, None, moves, contempt);
+                assert_eq!(
+                    score,
+                    Score::cp(-contempt),
+                    "contempt {} after {:?}",
+                    contempt,
+                    moves
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn hard_limit_is_a_multiple_of_the_soft_limit_up_to_the_cap() {
+        let clock = Duration::from_secs(60);
+        let allocate = |movestogo| {
+            let options = UciSearchOptions {
+                wtime: Some(clock),
+                movestogo,
+                ..Default::default()
+            };
+            allocate_time(&options, &Board::default()).unwrap()
+        };
+
+        let (soft, hard) = allocate(None);
+        assert_eq!(hard, soft * HARD_LIMIT_FACTOR);
+
+        // The last move before the control could use most of the clock, but both limits are capped
+        let cap = (clock - TIME_SAFETY_BUFFER) * MAX_TIME_FRACTION.0 / MAX_TIME_FRACTION.1;
+        assert_eq!(allocate(Some(1)), (cap, cap));
+    }
+
+    #[test]
+    fn unstable_iterations_extend_the_soft_limit_up_to_the_hard_limit() {
+        let mut engine = Engine::default();
+        let start = Instant::now();
+        engine.start_time = Some(start);
+        engine.soft_stop_time = Some(start + Duration::from_millis(100));
+        engine.stop_time = Some(start + Duration::from_millis(200));
+
+        engine.extend_soft_stop_time();
+        assert_eq!(
+            engine.soft_stop_time,
+            Some(start + Duration::from_millis(150))
+        );
+        engine.extend_soft_stop_time();
+        assert_eq!(engine.soft_stop_time, engine.stop_time);
+    }
+
+    #[test]
+    fn interrupted_iterations_only_count_moves_that_finished() {
+        let root_eval = |mv: &str, cp: i16, terminated_early: bool| BoardEvaluation {
+            mv: Some(ChessMove::from_str(mv).unwrap()),
+            depth: 5,
+            score: Score::cp(cp),
+            terminated_early,
+        };
+        let mut engine = Engine::default();
+        engine.best_move_found = Some(ChessMove::from_str("e2e4").unwrap());
+        let mut improvement = |root_evals: Vec<BoardEvaluation>| {
+            *engine.root_evals.get_mut() = root_evals;
+            engine.interrupted_improvement().and_then(|eval| eval.mv)
+        };
+
+        // The move that beat the previous best counts, but not the one whose search was cut short
+        assert_eq!(
+            improvement(vec![
+                root_eval("e2e4", 20, false),
+                root_eval("d2d4", 30, false),
+                root_eval("g1f3", 100, true),
+            ]),
+            Some(ChessMove::from_str("d2d4").unwrap())
+        );
+        // Nothing to compare against if the previous best move didn't finish
+        assert_eq!(
+            improvement(vec![
+                root_eval("e2e4", 20, true),
+                root_eval("d2d4", 30, false),
+            ]),
+            None
+        );
+        // Nothing beat the previous best move
+        assert_eq!(
+            improvement(vec![
+                root_eval("e2e4", 20, false),
+                root_eval("d2d4", 10, false),
+            ]),
+            None
+        );
+    }
+
+    #[test]
+    fn each_pruning_switch_changes_the_search() {
+        let pruned = nodes_with(PruningConfig::default());
+        // Killers and delta pruning have their own tests
+        let switches: [(&str, fn(&mut PruningConfig) -> &mut bool); 6] = [
+            ("null move", |pruning| &mut pruning.null_move),
+            ("lmr", |pruning| &mut pruning.lmr),
+            ("futility", |pruning| &mut pruning.futility),
+            ("razoring", |pruning| &mut pruning.razoring),
+            ("recapture", |pruning| &mut pruning.recapture),
+            ("see", |pruning| &mut pruning.see),
+        ];
+
+        for (name, switch) in switches {
+            let mut disabled = PruningConfig::default();
+            *switch(&mut disabled) = false;
+            assert_ne!(nodes_with(disabled), pruned, "{} made no difference", name);
+        }
+    }
+
+    #[test]
+    fn go_mate_stops_at_the_mate() {
+        // 1. Kc7 Ka7 2. Ra2#, found without a depth limit
+        let mut engine = engine_at("k7/8/2K5/8/8/8/7R/8 w - - 0 1");
+        let result = engine
+            .analyze(UciSearchOptions {
+                mate: Some(2),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(result.score, Score::mate(3), "{}", result.best_move);
+    }
+
+    #[test]
+    fn taking_the_last_piece_is_a_draw() {
+        // Taking the last knight leaves bare kings, which is just a draw (but better than staying a knight down)
+        let mut engine = engine_at("8/8/4k3/8/3n4/3K4/8/8 w - - 0 1");
+        let result = engine.analyze(to_depth(2)).unwrap();
+        assert_eq!(result.score, Score::cp(0));
+        assert_eq!(result.best_move, ChessMove::from_str("d3d4").unwrap());
+    }
+
+    #[test]
+    fn quiescence_takes_a_hanging_queen() {
+        let mut engine = engine_at("4k3/8/8/3q4/4P3/8/8/4K3 w - - 0 1");
+        let static_eval = eval_heuristic(engine.board(), &EvalParams::DEFAULT);
+
+        // Without any limits, the quiescence search runs to completion
+        let score = engine
+            .quiescence_eval(&UciSearchOptions::default())
+            .unwrap();
+        assert!(
+            static_eval < Score::cp(0) && score > Score::cp(0),
+            "{:?} then {:?}",
+            static_eval,
+            score
+        );
+    }
+
+    #[test]
+    fn short_movetime_still_gets_a_legal_move_right_away() {
+        let mut engine = Engine::default();
+        let start = Instant::now();
+        let mv = engine
+            .search(UciSearchOptions {
+                movetime: Some(Duration::from_millis(5)),
+                ..Default::default()
+            })
+            .unwrap();
+
+        assert!(engine.board().legal(mv), "{}", mv);
+        assert!(
+            start.elapsed() < Duration::from_secs(1),
+            "{:?}",
+            start.elapsed()
+        );
+    }
+
+    #[test]
+    fn tables_fit_in_the_hash_budget() {
+        for megabytes in [1, 16] {
+            let mut engine = Engine::default();
+            engine.set_hash_size(megabytes).unwrap();
+            // Nothing grows past the budget during a search
+            engine.search(to_depth(3)).unwrap();
+
+            // The transposition table takes up most of it
+            let budget = megabytes * MEGABYTE;
+            let usage = engine.memory_usage();
+            assert!(
+                usage <= budget && usage > budget * 9 / 10,
+                "{} of {} bytes",
+                usage,
+                budget
+            );
+        }
+    }
+}
@@ -0,0 +1,259 @@
+//! Move ordering, so that the most promising moves are searched first
+//!
+//! Alpha-beta search prunes the most when the best move is searched first,
+//! so every node sorts its moves by [`move_priority`] before searching them.
+
+use std::{
+    cmp::Reverse,
+    sync::atomic::{AtomicI32, Ordering},
+};
+
+use chess::{Board, ChessMove, EMPTY, NUM_SQUARES, Piece};
+
+use super::evaluation::piece_value;
+
+/// Priority bonus for promoting to a queen
+///
+/// This is almost always a winning move, so it is placed ahead of everything else
+const QUEEN_PROMOTION_PRIORITY: i32 = 100_000;
+
+/// Priority penalty for a quiet underpromotion that doesn't give check
+///
+/// These are almost never good moves, so they are placed behind all the quiet moves
+const QUIET_UNDERPROMOTION_PRIORITY: i32 = -10_000;
+
+/// Weight of the victim's value in [`capture_priority`], relative to the aggressor's
+///
+/// This is large enough that the victim always decides the order,
+/// and the aggressor only breaks ties between captures of the same piece
+const VICTIM_WEIGHT: i32 = 16;
+
+/// Priority of the killer moves (see [`KillerTable`]), in the order they are stored
+///
+/// These are ahead of the other quiet moves, but behind all the captures
+const KILLER_PRIORITY: [i32; 2] = [200, 100];
+
+/// Deepest ply that killer moves are tracked for
+pub const MAX_PLY: usize = 128;
+
+/// Killer moves: the last two quiet moves that caused a beta cutoff at each ply
+///
+/// A quiet move that refuted one line is often good in sibling lines too (e.g. a threat that wasn't parried),
+/// so killers are searched before the other quiet moves at the same ply.
+#[derive(Debug, Clone)]
+pub struct KillerTable([[Option<ChessMove>; 2]; MAX_PLY]);
+
+impl Default for KillerTable {
+    fn default() -> Self {
+        Self([[None; 2]; MAX_PLY])
+    }
+}
+
+impl KillerTable {
+    /// The killer moves at ply `depth`, most recent first
+    pub fn get(&self, depth: u8) -> [Option<ChessMove>; 2] {
+        self.0.get(depth as usize).copied().unwrap_or_default()
+    }
+
+    /// Records that the quiet move `mv` caused a beta cutoff at ply `depth`
+    pub fn store(&mut self, depth: u8, mv: ChessMove) {
+        let Some(killers) = self.0.get_mut(depth as usize) else {
+            return;
+        };
+
+        if killers[0] != Some(mv) {
+            killers[1] = killers[0];
+            killers[0] = Some(mv);
+        }
+    }
+
+    /// Forgets all the killer moves
+    pub fn clear(&mut self) {
+        self.0.fill([None; 2]);
+    }
+}
+
+/// History heuristic: how often each quiet move (by from and to square) has raised alpha, weighted by depth
+///
+/// Unlike killers, this isn't tied to a ply, so it picks up moves that are good all over the tree.
+/// Entries are atomic, so that all the search threads can update the table at once.
+#[derive(Debug)]
+pub struct HistoryTable(Vec<AtomicI32>);
+
+impl Default for HistoryTable {
+    fn default() -> Self {
+        Self((0..Self::ENTRIES).map(|_| AtomicI32::new(0)).collect())
+    }
+}
+
+impl HistoryTable {
+    /// Number of entries in the table, one for each pair of from and to squares
+    const ENTRIES: usize = NUM_SQUARES * NUM_SQUARES;
+
+    /// Memory used by the table, in bytes
+    pub const MEMORY_USAGE: usize = Self::ENTRIES * size_of::<AtomicI32>();
+
+    /// Slot of `mv` in the table
+    fn index(mv: ChessMove) -> usize {
+        mv.get_source().to_index() * NUM_SQUARES + mv.get_dest().to_index()
+    }
+
+    /// The history score of `mv`
+    pub fn get(&self, mv: ChessMove) -> i32 {
+        self.0[Self::index(mv)].load(Ordering::Relaxed)
+    }
+
+    /// Records that the quiet move `mv` raised alpha with `remaining` plies left to search
+    ///
+    /// Deeper searches are more trustworthy, so they count for more
+    pub fn record(&self, mv: ChessMove, remaining: u8) {
+        let bonus = remaining as i32 * remaining as i32;
+        // The closure always returns `Some`, so this can't fail
+        let _ =
+            self.0[Self::index(mv)].fetch_update(Ordering::Relaxed, Ordering::Relaxed, |score| {
+                Some(score.saturating_add(bonus))
+            });
+    }
+
+    /// Forgets all the history scores
+    pub fn clear(&mut self) {
+        for score in self.0.iter_mut() {
+            *score.get_mut() = 0;
+        }
+    }
+
+    /// Halves every entry, so that results from older searches gradually lose their influence
+    pub fn age(&mut self) {
+        for score in self.0.iter_mut() {
+            *score.get_mut() /= 2;
+        }
+    }
+}
+
+/// Sorts the provided moves in-place, so that the most promising moves come first
+///
+/// See [`move_priority`] for how moves are ranked, with the `killers` for this ply placed right after the captures.
+/// Ties (notably between the remaining quiet moves) are broken by the `history` table.
+pub fn order_moves(
+    board: &Board,
+    moves: &mut [ChessMove],
+    killers: [Option<ChessMove>; 2],
+    history: &HistoryTable,
+) {
+    moves.sort_by_cached_key(|mv| {
+        let killer = killers.iter().position(|killer| *killer == Some(*mv));
+        let priority = match killer {
+            Some(i) => KILLER_PRIORITY[i],
+            None => move_priority(board, *mv),
+        };
+
+        Reverse((priority, history.get(*mv)))
+    });
+}
+
+/// Heuristic priority of a move, where higher priorities should be searched first
+///
+/// - Queen promotions (capturing or not) come first
+/// - Captures come next, ordered by [`capture_priority`]
+/// - Underpromotions that capture or give check are treated like any other move
+/// - Quiet underpromotions come last
+pub fn move_priority(board: &Board, mv: ChessMove) -> i32 {
+    let capture = capture_priority(board, mv);
+
+    match mv.get_promotion() {
+        Some(Piece::Queen) => QUEEN_PROMOTION_PRIORITY + capture.unwrap_or(0),
+        Some(_) if capture.is_none() && *board.make_move_new(mv).checkers() == EMPTY => {
+            QUIET_UNDERPROMOTION_PRIORITY
+        }
+        _ => capture.unwrap_or(0),
+    }
+}
+
+/// Most Valuable Victim / Least Valuable Aggressor priority of a capture, or `None` if `mv` isn't a capture
+///
+/// Taking the most valuable piece is searched first, and among those, taking it with the least valuable piece.
+/// Every capture gets a positive priority, so they all come before the quiet moves.
+pub fn capture_priority(board: &Board, mv: ChessMove) -> Option<i32> {
+    let aggressor = board.piece_on(mv.get_source())?;
+    let victim = captured_piece(board, mv)?;
+
+    Some(VICTIM_WEIGHT * piece_value(victim) as i32 - piece_value(aggressor) as i32)
+}
+
+/// The piece captured by `mv`, or `None` if it isn't a capture
+pub fn captured_piece(board: &Board, mv: ChessMove) -> Option<Piece> {
+    match board.piece_on(mv.get_dest()) {
+        Some(victim) => Some(victim),
+        // En passant is the only way for a pawn to move diagonally onto an empty square
+        None if board.piece_on(mv.get_source()) == Some(Piece::Pawn)
+            && mv.get_source().get_file() != mv.get_dest().get_file() =>
+        {
+            Some(Piece::Pawn)
+        }
+        None => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use chess::MoveGen;
+
+    use super::*;
+
+    /// The legal moves on `fen`, in the order they are searched without any killers or history
+    fn ordered(fen: &str) -> Vec<ChessMove> {
+        let board = Board::from_str(fen).unwrap();
+        let mut moves: Vec<_> = MoveGen::new_legal(&board).collect();
+        order_moves(&board, &mut moves, [None; 2], &HistoryTable::default());
+        moves
+    }
+
+    #[test]
+    fn queen_promotions_first_and_quiet_underpromotions_last() {
+        // b7 can promote quietly on b8, or by taking the rook on a8
+        let moves = ordered("r3k3/1P6/8/8/8/8/8/4K2R w - - 0 1");
+        let promotions: Vec<_> = moves.iter().map(|mv| mv.get_promotion()).collect();
+
+        // Taking the rook while promoting to a queen is the best of all
+        assert_eq!(moves[0], ChessMove::from_str("b7a8q").unwrap());
+        assert_eq!(promotions[1], Some(Piece::Queen));
+        // Promoting to a knight or a bishop doesn't give check here, unlike promoting to a rook
+        let mut last: Vec<_> = promotions[promotions.len() - 2..].to_vec();
+        last.sort_by_key(|piece| piece.map(Piece::to_index));
+        assert_eq!(
+            last,
+            [Some(Piece::Knight), Some(Piece::Bishop)],
+            "{:?}",
+            moves
+        );
+    }
+
+    #[test]
+    fn captures_take_the_most_valuable_victim_with_the_least_valuable_aggressor() {
+        // The queen on d5 can be taken by the pawn on e4 or the knight on c3, and the pawn on b5 by the knight
+        let moves = ordered("4k3/8/8/1p1q4/4P3/2N5/8/4K3 w - - 0 1");
+
+        let expected = ["e4d5", "c3d5", "c3b5"].map(|mv| ChessMove::from_str(mv).unwrap());
+        assert_eq!(moves[..3], expected, "{:?}", moves);
+    }
+
+    #[test]
+    fn history_breaks_ties_between_quiet_moves_and_halves_with_age() {
+        let board = Board::default();
+        let mut history = HistoryTable::default();
+        let mv = ChessMove::from_str("b1c3").unwrap();
+        history.record(mv, 4);
+        assert_eq!(history.get(mv), 16);
+
+        let mut moves: Vec<_> = MoveGen::new_legal(&board).collect();
+        order_moves(&board, &mut moves, [None; 2], &history);
+        assert_eq!(moves[0], mv, "{:?}", moves);
+
+        history.age();
+        assert_eq!(history.get(mv), 8);
+        history.clear();
+        assert_eq!(history.get(mv), 0);
+    }
+}
@@ -0,0 +1,253 @@
+//! UCI options supported by the [`Engine`]
+
+use std::{path::Path, time::Duration};
+
+use anyhow::{Context, bail};
+use uci_parser::UciOption;
+
+use crate::uci_log;
+
+use super::{
+    DEFAULT_MOVE_OVERHEAD, Engine, PruningConfig, max_threads,
+    transposition::{DEFAULT_HASH_MB, MAX_HASH_MB},
+};
+
+/// Name of the option setting [`super::EngineConfig::move_overhead`], in milliseconds
+const MOVE_OVERHEAD: &str = "Move Overhead";
+
+/// Name of the option setting [`super::EngineConfig::threads`]
+const THREADS: &str = "Threads";
+
+/// Name of the option setting [`super::EngineConfig::hash_mb`]
+const HASH: &str = "Hash";
+
+/// Name of the option setting [`super::EngineConfig::multi_pv`]
+const MULTI_PV: &str = "MultiPV";
+
+/// Largest number of lines that can be requested with [`MULTI_PV`] or [`CANDIDATES`]
+const MAX_MULTI_PV: usize = 256;
+
+/// Name of the option setting [`super::EngineConfig::candidates`]
+const CANDIDATES: &str = "Candidates";
+
+/// Name of the option setting [`super::EngineConfig::contempt`], in centipawns
+const CONTEMPT: &str = "Contempt";
+
+/// Largest contempt (either way) that can be set with [`CONTEMPT`], in centipawns
+const MAX_CONTEMPT: i16 = 500;
+
+/// Name of the option setting [`super::EngineConfig::ponder`]
+const PONDER: &str = "Ponder";
+
+/// Name of the button clearing the transposition table
+const CLEAR_HASH: &str = "Clear Hash";
+
+/// Name of the option setting [`super::EngineConfig::seed`]
+const SEED: &str = "Seed";
+
+/// Name of the option setting [`super::EngineConfig::chess960`]
+const UCI_CHESS960: &str = "UCI_Chess960";
+
+/// Name of the option setting the file that UCI traffic is mirrored to (see [`uci_log`])
+const DEBUG_LOG_FILE: &str = "Debug Log File";
+
+/// Name of the option toggling [`PruningConfig::null_move`]
+const NULL_MOVE: &str = "NullMove";
+
+/// Name of the option toggling [`PruningConfig::lmr`]
+const LMR: &str = "LMR";
+
+/// Name of the option toggling [`PruningConfig::futility`]
+const FUTILITY: &str = "Futility";
+
+/// Name of the option toggling [`PruningConfig::razoring`]
+const RAZORING: &str = "Razoring";
+
+/// Name of the option toggling [`PruningConfig::recapture`]
+const RECAPTURE_REDUCTION: &str = "RecaptureReduction";
+
+/// Name of the option toggling [`PruningConfig::delta`]
+const DELTA_PRUNING: &str = "DeltaPruning";
+
+/// Name of the option toggling [`PruningConfig::see`]
+const SEE_PRUNING: &str = "SeePruning";
+
+/// Name of the option toggling [`PruningConfig::killers`]
+const KILLERS: &str = "Killers";
+
+impl Engine {
+    /// All the options the engine supports, to be advertised during the `uci` handshake
+    pub fn options() -> Vec<UciOption> {
+        let pruning = PruningConfig::default();
+
+        vec![
+            UciOption::spin(
+                MOVE_OVERHEAD.to_string(),
+                DEFAULT_MOVE_OVERHEAD.as_millis() as i32,
+                0,
+                5000,
+            ),
+            UciOption::spin(
+                THREADS.to_string(),
+                max_threads() as i32,
+                1,
+                max_threads() as i32,
+            ),
+            UciOption::spin(
+                HASH.to_string(),
+                DEFAULT_HASH_MB as i32,
+                1,
+                MAX_HASH_MB as i32,
+            ),
+            UciOption::button(CLEAR_HASH.to_string()),
+            UciOption::spin(MULTI_PV.to_string(), 1, 1, MAX_MULTI_PV as i32),
+            UciOption::spin(CANDIDATES.to_string(), 0, 0, MAX_MULTI_PV as i32),
+            UciOption::spin(
+                CONTEMPT.to_string(),
+                0,
+                -MAX_CONTEMPT as i32,
+                MAX_CONTEMPT as i32,
+            ),
+            UciOption::check(PONDER.to_string(), false),
+            UciOption::string(SEED.to_string(), ""),
+            UciOption::check(UCI_CHESS960.to_string(), false),
+            UciOption::string(DEBUG_LOG_FILE.to_string(), ""),
+            UciOption::check(NULL_MOVE.to_string(), pruning.null_move),
+            UciOption::check(LMR.to_string(), pruning.lmr),
+            UciOption::check(FUTILITY.to_string(), pruning.futility),
+            UciOption::check(RAZORING.to_string(), pruning.razoring),
+            UciOption::check(RECAPTURE_REDUCTION.to_string(), pruning.recapture),
+            UciOption::check(DELTA_PRUNING.to_string(), pruning.delta),
+            UciOption::check(SEE_PRUNING.to_string(), pruning.see),
+            UciOption::check(KILLERS.to_string(), pruning.killers),
+        ]
+    }
+
+    /// Handles a `setoption` command
+    ///
+    /// Option names are matched case-insensitively, as recommended by the UCI specification
+    pub fn set_option(&mut self, name: &str, value: Option<&str>) -> anyhow::Result<()> {
+        if name.eq_ignore_ascii_case(MOVE_OVERHEAD) {
+            let millis = parse_value::<u64>(name, value)?;
+            self.set_move_overhead(Duration::from_millis(millis));
+        } else if name.eq_ignore_ascii_case(THREADS) {
+            self.set_threads(parse_value(name, value)?);
+        } else if name.eq_ignore_ascii_case(HASH) {
+            self.set_hash_size(parse_value(name, value)?)?;
+        } else if name.eq_ignore_ascii_case(CLEAR_HASH) {
+            self.clear_hash();
+        } else if name.eq_ignore_ascii_case(MULTI_PV) {
+            let lines = parse_value::<usize>(name, value)?;
+            self.set_multi_pv(lines.min(MAX_MULTI_PV));
+        } else if name.eq_ignore_ascii_case(CANDIDATES) {
+            let candidates = parse_value::<usize>(name, value)?;
+            self.set_candidates(candidates.min(MAX_MULTI_PV));
+        } else if name.eq_ignore_ascii_case(CONTEMPT) {
+            let contempt = parse_value::<i16>(name, value)?;
+            self.set_contempt(contempt.clamp(-MAX_CONTEMPT, MAX_CONTEMPT));
+        } else if name.eq_ignore_ascii_case(PONDER) {
+            self.set_ponder(parse_value(name, value)?);
+        } else if name.eq_ignore_ascii_case(SEED) {
+            // An empty value (which GUIs may send as `<empty>`) leaves the engine unseeded
+            let seed = value
+                .map(str::trim)
+                .filter(|seed| !seed.is_empty() && *seed != "<empty>");
+            self.config.seed = seed.map(|_| parse_value(name, seed)).transpose()?;
+        } else if name.eq_ignore_ascii_case(UCI_CHESS960) {
+            self.set_chess960(parse_value(name, value)?);
+        } else if name.eq_ignore_ascii_case(DEBUG_LOG_FILE) {
+            // An empty value (which GUIs may send as `<empty>`) turns logging off
+            let path = value
+                .map(str::trim)
+                .filter(|path| !path.is_empty() && *path != "<empty>");
+            uci_log::set_file(path.map(Path::new)).with_context(|| {
+                format!(
+                    "Failed to open debug log file: {}",
+                    path.unwrap_or_default()
+                )
+            })?;
+        } else if name.eq_ignore_ascii_case(NULL_MOVE) {
+            self.config.pruning.null_move = parse_value(name, value)?;
+        } else if name.eq_ignore_ascii_case(LMR) {
+            self.config.pruning.lmr = parse_value(name, value)?;
+        } else if name.eq_ignore_ascii_case(FUTILITY) {
+            self.config.pruning.futility = parse_value(name, value)?;
+        } else if name.eq_ignore_ascii_case(RAZORING) {
+            self.config.pruning.razoring = parse_value(name, value)?;
+        } else if name.eq_ignore_ascii_case(RECAPTURE_REDUCTION) {
+            self.config.pruning.recapture = parse_value(name, value)?;
+        } else if name.eq_ignore_ascii_case(DELTA_PRUNING) {
+            self.config.pruning.delta = parse_value(name, value)?;
+        } else if name.eq_ignore_ascii_case(SEE_PRUNING) {
+            self.config.pruning.see = parse_value(name, value)?;
+        } else if name.eq_ignore_ascii_case(KILLERS) {
+            self.config.pruning.killers = parse_value(name, value)?;
+        } else {
+            bail!("Unknown option: {}", name);
+        }
+
+        Ok(())
+    }
+}
+
+/// Parses the value of option `name`, failing if it is missing or malformed
+fn parse_value<T: std::str::FromStr>(name: &str, value: Option<&str>) -> anyhow::Result<T> {
+    value
+        .context(format!("Missing value for option: {}", name))?
+        .trim()
+        .parse()
+        .ok()
+        .context(format!("Invalid value for option: {}", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use uci_parser::{UciResponse, UciSearchOptions};
+
+    use super::*;
+
+    #[test]
+    fn clear_hash_empties_the_table() {
+        let mut engine = Engine::default();
+        engine.set_hash_size(1).unwrap();
+        engine
+            .search(UciSearchOptions {
+                depth: Some(5),
+                ..Default::default()
+            })
+            .unwrap();
+        assert!(engine.hashfull() > 0);
+
+        engine.set_option(CLEAR_HASH, None).unwrap();
+        assert_eq!(engine.hashfull(), 0);
+    }
+
+    #[test]
+    fn multi_pv_is_parsed_and_capped() {
+        let mut engine = Engine::default();
+        engine.set_option("multipv", Some(" 3 ")).unwrap();
+        assert_eq!(engine.config().multi_pv, 3);
+
+        engine.set_option(MULTI_PV, Some("100000")).unwrap();
+        assert_eq!(engine.config().multi_pv, MAX_MULTI_PV);
+
+        assert!(engine.set_option(MULTI_PV, Some("many")).is_err());
+        assert!(engine.set_option(MULTI_PV, None).is_err());
+        assert_eq!(engine.config().multi_pv, MAX_MULTI_PV);
+    }
+
+    #[test]
+    fn ponder_is_advertised_and_settable() {
+        // GUIs probe the handshake for the Ponder option before letting us ponder
+        let advertised = Engine::options().into_iter().any(|option| {
+            UciResponse::Option(option)
+                .to_string()
+                .starts_with("option name Ponder type check")
+        });
+        assert!(advertised);
+
+        let mut engine = Engine::default();
+        engine.set_option(PONDER, Some("true")).unwrap();
+        assert!(engine.config().ponder);
+    }
+}
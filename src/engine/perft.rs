@@ -0,0 +1,36 @@
+//! Move generation node counts, for validating the move generator (see [`perft`])
+//!
+//! Known counts for well-known positions are listed on the [chessprogramming wiki](https://www.chessprogramming.org/Perft_Results).
+
+use chess::{Board, ChessMove, MoveGen};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+/// Number of leaf nodes in the tree of legal moves `depth` plies deep from `board`
+pub fn perft(board: &Board, depth: u8) -> u64 {
+    let moves = MoveGen::new_legal(board);
+
+    match depth {
+        0 => 1,
+        // The leaves don't need to be made, just counted
+        1 => moves.len() as u64,
+        _ => moves
+            .map(|mv| perft(&board.make_move_new(mv), depth - 1))
+            .sum(),
+    }
+}
+
+/// [`perft`] split by root move, with each root move's subtree counted in parallel
+///
+/// Comparing these against another move generator narrows a mismatch down to a single move.
+/// Moves are in move generation order, and there are none at depth zero.
+pub fn perft_divide(board: &Board, depth: u8) -> Vec<(ChessMove, u64)> {
+    if depth == 0 {
+        return Vec::new();
+    }
+
+    MoveGen::new_legal(board)
+        .collect::<Vec<_>>()
+        .into_par_iter()
+        .map(|mv| (mv, perft(&board.make_move_new(mv), depth - 1)))
+        .collect()
+}
@@ -0,0 +1,102 @@
+//! Static Exchange Evaluation, see [`see`]
+
+use chess::{
+    ALL_PIECES, BitBoard, Board, ChessMove, Color, EMPTY, Piece, Square, get_bishop_moves,
+    get_king_moves, get_knight_moves, get_pawn_attacks, get_rook_moves,
+};
+
+use super::{evaluation::piece_value, move_ordering::captured_piece};
+
+/// Value of the king in an exchange
+///
+/// The king can only be the last piece to capture, as capturing it would end the game,
+/// so it is worth more than everything else combined
+const KING_VALUE: i32 = 20_000;
+
+/// Most captures that can happen on a single square (every piece but the kings, plus some headroom)
+const MAX_EXCHANGES: usize = 32;
+
+/// Net material (in centipawns) won by `mv` for the player making it, once all the captures on its destination are played out
+///
+/// Each side recaptures with its least valuable piece, and can stop capturing whenever continuing would lose material.
+/// Pieces behind the capturers (like a rook behind a rook) join in as the pieces in front of them are traded off.
+/// Pins and checks are not considered, so this is an estimate, but a cheap and usually accurate one.
+pub fn see(board: &Board, mv: ChessMove) -> i16 {
+    let target = mv.get_dest();
+    let Some(mut attacker) = board.piece_on(mv.get_source()) else {
+        return 0;
+    };
+
+    let mut occupied = *board.combined() ^ BitBoard::from_square(mv.get_source());
+    let mut gain = [0; MAX_EXCHANGES];
+    gain[0] = captured_piece(board, mv).map_or(0, value);
+
+    // The pawn taken en passant isn't on the target square
+    if board.piece_on(target).is_none() && gain[0] != 0 {
+        let captured = Square::make_square(mv.get_source().get_rank(), target.get_file());
+        occupied ^= BitBoard::from_square(captured);
+    }
+
+    if let Some(promotion) = mv.get_promotion() {
+        gain[0] += value(promotion) - value(Piece::Pawn);
+        attacker = promotion;
+    }
+
+    // Play out the exchange, recording the gain for the side capturing at each step,
+    // as if they were the last to capture
+    let mut side = !board.side_to_move();
+    let mut depth = 0;
+    while depth + 1 < MAX_EXCHANGES {
+        let Some((square, piece)) = least_valuable_attacker(board, target, side, occupied) else {
+            break;
+        };
+
+        depth += 1;
+        gain[depth] = value(attacker) - gain[depth - 1];
+
+        occupied ^= BitBoard::from_square(square);
+        attacker = piece;
+        side = !side;
+    }
+
+    // Then walk back, letting each side stop capturing when that is better for them
+    while depth > 0 {
+        gain[depth - 1] = -(-gain[depth - 1]).max(gain[depth]);
+        depth -= 1;
+    }
+
+    gain[0].clamp(i16::MIN as i32, i16::MAX as i32) as i16
+}
+
+/// Value of `piece` for exchanges
+fn value(piece: Piece) -> i32 {
+    match piece {
+        Piece::King => KING_VALUE,
+        piece => piece_value(piece) as i32,
+    }
+}
+
+/// The least valuable piece of `color` attacking `square`, considering only the pieces in `occupied`
+fn least_valuable_attacker(
+    board: &Board,
+    square: Square,
+    color: Color,
+    occupied: BitBoard,
+) -> Option<(Square, Piece)> {
+    let ours = *board.color_combined(color) & occupied;
+
+    ALL_PIECES.into_iter().find_map(|piece| {
+        let attacks = match piece {
+            // Pawns attacking `square` are where a pawn of the other color on `square` would attack
+            Piece::Pawn => get_pawn_attacks(square, !color, !EMPTY),
+            Piece::Knight => get_knight_moves(square),
+            Piece::Bishop => get_bishop_moves(square, occupied),
+            Piece::Rook => get_rook_moves(square, occupied),
+            Piece::Queen => get_bishop_moves(square, occupied) | get_rook_moves(square, occupied),
+            Piece::King => get_king_moves(square),
+        };
+
+        let attackers = attacks & *board.pieces(piece) & ours;
+        (attackers != EMPTY).then(|| (attackers.to_square(), piece))
+    })
+}
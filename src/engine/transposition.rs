@@ -0,0 +1,160 @@
+//! See [`TranspositionTable`]
+
+use std::collections::TryReserveError;
+
+use chess::ChessMove;
+
+use crate::score::Score;
+
+/// Default size of the table, in megabytes
+pub const DEFAULT_HASH_MB: usize = 16;
+
+/// Largest size of the table that can be requested, in megabytes
+pub const MAX_HASH_MB: usize = 65536;
+
+/// Number of bytes in a megabyte
+pub const MEGABYTE: usize = 1024 * 1024;
+
+/// How the score of a [`TranspositionData`] relates to the true score of its position
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeType {
+    /// The score is exact, as it fell within the search window
+    Pv,
+    /// No move raised alpha, so the score is an upper bound
+    All,
+    /// A move reached beta, so the score is a lower bound
+    Cut,
+}
+
+/// The result of searching a position, as stored in the [`TranspositionTable`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TranspositionData {
+    /// The best move found, if any
+    pub best_move: Option<ChessMove>,
+    /// The score of the position, from the perspective of the player up to move
+    ///
+    /// Mate scores count plies from this position, so they don't depend on where in the tree it was found
+    pub score: Score,
+    /// What kind of bound `score` is
+    pub node_type: NodeType,
+    /// The number of plies that were searched below this position, before the quiescence search
+    pub depth: u8,
+}
+
+/// A slot in the table, holding the full hash of its position to detect collisions
+type Entry = Option<(u64, TranspositionData)>;
+
+/// Cache of previously searched positions, keyed by their hash
+///
+/// The same position is often reached through different move orders,
+/// so this lets the search reuse results instead of searching those positions again.
+///
+/// The table has a fixed number of slots, determined by its size in memory.
+/// Each position can only go in one slot, so when two positions compete for a slot,
+/// the one that was searched more deeply is kept, as it is more expensive to recompute.
+#[derive(Debug)]
+pub struct TranspositionTable {
+    /// The slots of the table, which are only allocated on first use
+    entries: Vec<Entry>,
+    /// Number of slots in the table
+    capacity: usize,
+    /// Number of slots that are in use
+    filled: usize,
+}
+
+impl Default for TranspositionTable {
+    /// Constructs a table of [`DEFAULT_HASH_MB`] megabytes, which is allocated on first use
+    fn default() -> Self {
+        Self::new(DEFAULT_HASH_MB * MEGABYTE)
+    }
+}
+
+impl TranspositionTable {
+    /// Constructs a table taking up at most `bytes` of memory, which is allocated on first use (see [`Self::allocate`])
+    pub fn new(bytes: usize) -> Self {
+        Self {
+            entries: Vec::new(),
+            capacity: Self::capacity_for(bytes),
+            filled: 0,
+        }
+    }
+
+    /// Allocates the table, if it isn't already
+    ///
+    /// Large tables may not fit in memory, so this reports the failure instead of aborting the process.
+    pub fn allocate(&mut self) -> Result<(), TryReserveError> {
+        if self.entries.is_empty() {
+            self.entries.try_reserve_exact(self.capacity)?;
+            self.entries.resize(self.capacity, None);
+        }
+
+        Ok(())
+    }
+
+    /// Number of slots that fit in `bytes` of memory (always at least one)
+    fn capacity_for(bytes: usize) -> usize {
+        (bytes / size_of::<Entry>()).max(1)
+    }
+
+    /// Slot that the position with `hash` goes into
+    fn index(&self, hash: u64) -> usize {
+        (hash % self.capacity as u64) as usize
+    }
+
+    /// Gets the stored result for the position with `hash`, if any
+    pub fn get(&self, hash: u64) -> Option<TranspositionData> {
+        match self.entries.get(self.index(hash)) {
+            Some(Some((stored, data))) if *stored == hash => Some(*data),
+            _ => None,
+        }
+    }
+
+    /// Stores the result of searching the position with `hash`
+    ///
+    /// An existing entry (for this or another position) is only replaced if the new one was searched at least as deeply.
+    /// If the table can't be allocated, nothing is stored.
+    pub fn insert(&mut self, hash: u64, data: TranspositionData) {
+        if self.allocate().is_err() {
+            return;
+        }
+
+        let index = self.index(hash);
+        let slot = &mut self.entries[index];
+        match slot {
+            Some((_, existing)) if existing.depth > data.depth => {}
+            Some(_) => *slot = Some((hash, data)),
+            None => {
+                *slot = Some((hash, data));
+                self.filled += 1;
+            }
+        }
+    }
+
+    /// Removes all the stored results
+    pub fn clear(&mut self) {
+        self.entries.fill(None);
+        self.filled = 0;
+    }
+
+    /// Number of positions stored
+    pub fn len(&self) -> usize {
+        self.filled
+    }
+
+    /// Whether no positions are stored
+    pub fn is_empty(&self) -> bool {
+        self.filled == 0
+    }
+
+    /// How full the table is, in permille, as reported by the UCI `hashfull` info
+    pub fn hashfull(&self) -> usize {
+        self.filled * 1000 / self.capacity
+    }
+
+    /// Memory used by the table, in bytes
+    ///
+    /// This is the full size of the table once it is allocated, regardless of how many positions are stored
+    pub fn memory_usage(&self) -> usize {
+        self.entries.capacity() * size_of::<Entry>()
+    }
+}
@@ -0,0 +1,155 @@
+//! The engine's own Zobrist hashing scheme
+//!
+//! [`Board::get_hash`] works fine, but its keys belong to the `chess` crate, and may change between versions.
+//! The keys here are generated from a fixed seed at compile time,
+//! so hashes are identical across runs, platforms, and dependency updates.
+//! This keeps things like `bench` node counts and saved hash tables reproducible.
+
+use chess::{ALL_PIECES, Board, Color, NUM_CASTLE_RIGHTS, NUM_COLORS, NUM_PIECES, NUM_SQUARES};
+
+/// Version of the key set
+///
+/// Bump this whenever any hash would change: a new [`SEED`], a change to how the keys are generated (including their order),
+/// or a change to which keys [`hash`] combines. The `zobrist` check in `--selftest` pins the hash of the starting position
+/// along with this version, so it fails until both are updated together.
+///
+/// Hashes are only meaningful under the version they were computed with.
+/// Anything that saves them (such as a transposition table dumped to disk) must store this version alongside,
+/// and discard the saved data when it differs, as its lookups would otherwise silently miss or hit the wrong positions.
+pub const ZOBRIST_KEY_VERSION: u32 = 1;
+
+/// Seed for the key generator
+const SEED: u64 = 0x7061_7463_685f_7a62; // "patch_zb"
+
+/// Selects how positions are hashed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HashScheme {
+    /// [`Board::get_hash`], from the `chess` crate
+    #[default]
+    Chess,
+    /// The engine's own keys (see [`hash`])
+    Zobrist,
+}
+
+impl HashScheme {
+    /// Hashes `board` with this scheme
+    pub fn hash(self, board: &Board) -> u64 {
+        match self {
+            HashScheme::Chess => board.get_hash(),
+            HashScheme::Zobrist => hash(board),
+        }
+    }
+}
+
+/// All the keys making up the hashing scheme
+struct Keys {
+    /// Indexed by color, piece, then square
+    pieces: [[[u64; NUM_SQUARES]; NUM_PIECES]; NUM_COLORS],
+    /// Indexed by color, then castle rights
+    castling: [[u64; NUM_CASTLE_RIGHTS]; NUM_COLORS],
+    /// Indexed by the file of the en passant square
+    en_passant: [u64; 8],
+    /// Toggled when black is up to move
+    black_to_move: u64,
+}
+
+static KEYS: Keys = generate_keys();
+
+/// Hashes `board` with the engine's own keys
+///
+/// Like [`Board::get_hash`], this covers piece placement, castle rights, the en passant file, and the side to move
+pub fn hash(board: &Board) -> u64 {
+    let mut hash = 0;
+
+    for color in [Color::White, Color::Black] {
+        let ours = board.color_combined(color);
+        for piece in ALL_PIECES {
+            for square in *board.pieces(piece) & *ours {
+                hash ^= KEYS.pieces[color.to_index()][piece.to_index()][square.to_index()];
+            }
+        }
+
+        hash ^= KEYS.castling[color.to_index()][board.castle_rights(color).to_index()];
+    }
+
+    if let Some(square) = board.en_passant() {
+        hash ^= KEYS.en_passant[square.get_file().to_index()];
+    }
+
+    if board.side_to_move() == Color::Black {
+        hash ^= KEYS.black_to_move;
+    }
+
+    hash
+}
+
+/// Generates all the keys from [`SEED`]
+const fn generate_keys() -> Keys {
+    let mut state = SEED;
+    let mut keys = Keys {
+        pieces: [[[0; NUM_SQUARES]; NUM_PIECES]; NUM_COLORS],
+        castling: [[0; NUM_CASTLE_RIGHTS]; NUM_COLORS],
+        en_passant: [0; 8],
+        black_to_move: 0,
+    };
+
+    // Loops must be written out by hand, as iterators can't be used in const contexts
+    let mut color = 0;
+    while color < NUM_COLORS {
+        let mut piece = 0;
+        while piece < NUM_PIECES {
+            let mut square = 0;
+            while square < NUM_SQUARES {
+                keys.pieces[color][piece][square] = next_key(&mut state);
+                square += 1;
+            }
+            piece += 1;
+        }
+
+        let mut rights = 0;
+        while rights < NUM_CASTLE_RIGHTS {
+            keys.castling[color][rights] = next_key(&mut state);
+            rights += 1;
+        }
+        color += 1;
+    }
+
+    let mut file = 0;
+    while file < 8 {
+        keys.en_passant[file] = next_key(&mut state);
+        file += 1;
+    }
+
+    keys.black_to_move = next_key(&mut state);
+
+    keys
+}
+
+/// Advances `state` and returns the next key, using SplitMix64
+///
+/// This is tiny, well-distributed, and entirely specified here, so the keys never depend on an outside RNG
+const fn next_key(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The key version, and the hash of the starting position under it
+    ///
+    /// If the keys change, both must be updated together (see [`ZOBRIST_KEY_VERSION`])
+    const STARTPOS_ZOBRIST: (u32, u64) = (1, 0x083f_64f6_3c32_e876);
+
+    #[test]
+    fn keys_only_change_with_the_version() {
+        assert_eq!(
+            (ZOBRIST_KEY_VERSION, hash(&Board::default())),
+            STARTPOS_ZOBRIST
+        );
+    }
+}
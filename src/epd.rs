@@ -0,0 +1,340 @@
+//! Test suites in the Extended Position Description format, like Win At Chess or the Bratko-Kopec test
+//!
+//! Each line is the first four fields of a FEN, followed by `;`-terminated operations.
+//! Only the `bm` (best moves), `am` (moves to avoid), and `id` operations are used, and the rest are ignored.
+
+use std::{fmt, fs, path::Path, str::FromStr, sync::atomic::Ordering, time::Duration};
+
+use anyhow::{Context, bail};
+use chess::{Board, ChessMove};
+use uci_parser::UciSearchOptions;
+
+use crate::engine::{Engine, EngineConfig, evaluation::EvalParams};
+
+/// A single test position from an EPD file
+#[derive(Debug, Clone)]
+pub struct EpdEntry {
+    /// The position
+    pub board: Board,
+    /// Name of the position, from the `id` operation
+    pub id: Option<String>,
+    /// Moves that pass the test, from the `bm` operation
+    pub best_moves: Vec<ChessMove>,
+    /// Moves that fail the test, from the `am` operation
+    pub avoid_moves: Vec<ChessMove>,
+}
+
+impl EpdEntry {
+    /// Whether `mv` passes this test: it must be one of the best moves (if any), and none of the moves to avoid
+    pub fn passes(&self, mv: ChessMove) -> bool {
+        (self.best_moves.is_empty() || self.best_moves.contains(&mv))
+            && !self.avoid_moves.contains(&mv)
+    }
+}
+
+impl FromStr for EpdEntry {
+    type Err = anyhow::Error;
+
+    fn from_str(line: &str) -> anyhow::Result<Self> {
+        let mut fields = line.splitn(5, ' ');
+        let position: Vec<_> = fields.by_ref().take(4).collect();
+        if position.len() < 4 {
+            bail!("Expected at least 4 FEN fields, got {}", line);
+        }
+
+        // EPD leaves out the move counters, as they don't matter for analysis
+        let fen = format!("{} 0 1", position.join(" "));
+        let board = Board::from_str(&fen).map_err(anyhow::Error::msg)?;
+
+        let mut entry = Self {
+            board,
+            id: None,
+            best_moves: Vec::new(),
+            avoid_moves: Vec::new(),
+        };
+
+        let operations = fields.next().unwrap_or_default();
+        for operation in operations.split(';').map(str::trim) {
+            let (opcode, operands) = operation.split_once(' ').unwrap_or((operation, ""));
+            match opcode {
+                "bm" => entry.best_moves = parse_moves(&board, operands)?,
+                "am" => entry.avoid_moves = parse_moves(&board, operands)?,
+                "id" => entry.id = Some(operands.trim().trim_matches('"').to_string()),
+                _ => {}
+            }
+        }
+
+        Ok(entry)
+    }
+}
+
+/// Parses space-separated moves in Standard Algebraic Notation, like `Nf3` or `Qxh7+`
+fn parse_moves(board: &Board, operands: &str) -> anyhow::Result<Vec<ChessMove>> {
+    operands
+        .split_whitespace()
+        .map(|san| {
+            // Check and annotation suffixes aren't needed to identify the move
+            let stripped = san.trim_end_matches(['+', '#', '!', '?']);
+            ChessMove::from_san(board, stripped)
+                .map_err(anyhow::Error::msg)
+                .with_context(|| format!("Invalid move {} in position {}", san, board))
+        })
+        .collect()
+}
+
+/// Parses every non-empty line of `contents` as an [`EpdEntry`]
+pub fn parse(contents: &str) -> anyhow::Result<Vec<EpdEntry>> {
+    contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            line.trim()
+                .parse()
+                .with_context(|| format!("Invalid EPD on line {}", i + 1))
+        })
+        .collect()
+}
+
+/// Why a position failed, as found by searching it again more deeply (see [`classify_failure`])
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureKind {
+    /// The deeper search passes, so the search just didn't get far enough
+    Depth,
+    /// The deeper search still fails, so the evaluation likely misjudges the position
+    Eval,
+}
+
+impl fmt::Display for FailureKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FailureKind::Depth => write!(f, "depth"),
+            FailureKind::Eval => write!(f, "eval"),
+        }
+    }
+}
+
+/// Searches `entry` from a fresh game with `options`, returning the best move
+///
+/// Each position is searched from a fresh game, so that results don't depend on what was searched before.
+pub fn search_entry(
+    engine: &mut Engine,
+    entry: &EpdEntry,
+    options: UciSearchOptions,
+) -> anyhow::Result<ChessMove> {
+    engine.reset_game();
+    engine.set_position_from_board(entry.board);
+    engine.stop_flag().store(false, Ordering::Relaxed);
+
+    engine.search(options)
+}
+
+/// Searches a failed `entry` again to `depth`, to tell whether it failed because of the search depth or the evaluation
+///
+/// Returns the kind of failure, and the move found by the deeper search.
+pub fn classify_failure(
+    engine: &mut Engine,
+    entry: &EpdEntry,
+    depth: u8,
+) -> anyhow::Result<(FailureKind, ChessMove)> {
+    let mv = search_entry(
+        engine,
+        entry,
+        UciSearchOptions {
+            depth: Some(depth as u32),
+            ..Default::default()
+        },
+    )?;
+
+    let kind = if entry.passes(mv) {
+        FailureKind::Depth
+    } else {
+        FailureKind::Eval
+    };
+    Ok((kind, mv))
+}
+
+/// Searches every position in the EPD file at `path` for `movetime`, printing whether each one passed
+///
+/// If `recheck_depth` is set, each failed position is searched again to that depth, and the failure is classified (see [`classify_failure`]).
+/// Returns the number of positions that passed, and the total number of positions.
+pub fn run_suite(
+    engine: &mut Engine,
+    path: &Path,
+    movetime: Duration,
+    recheck_depth: Option<u8>,
+) -> anyhow::Result<(usize, usize)> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read test suite {}", path.display()))?;
+    let entries = parse(&contents)?;
+
+    let mut passed = 0;
+    let mut depth_failures = 0;
+    for (i, entry) in entries.iter().enumerate() {
+        let mv = search_entry(
+            engine,
+            entry,
+            UciSearchOptions {
+                movetime: Some(movetime),
+                ..Default::default()
+            },
+        )?;
+
+        let name = entry.id.clone().unwrap_or_else(|| format!("#{}", i + 1));
+        if entry.passes(mv) {
+            passed += 1;
+            println!("PASS {}: {}", name, mv);
+        } else if let Some(depth) = recheck_depth {
+            let (kind, deeper) = classify_failure(engine, entry, depth)?;
+            if kind == FailureKind::Depth {
+                depth_failures += 1;
+            }
+            println!(
+                "FAIL {}: {} ({} failure: {} at depth {})",
+                name, mv, kind, deeper, depth
+            );
+        } else {
+            println!("FAIL {}: {}", name, mv);
+        }
+    }
+
+    if recheck_depth.is_some() {
+        let failed = entries.len() - passed;
+        println!(
+            "{}/{} passed ({} depth failures, {} eval failures)",
+            passed,
+            entries.len(),
+            depth_failures,
+            failed - depth_failures
+        );
+    } else {
+        println!("{}/{} passed", passed, entries.len());
+    }
+    Ok((passed, entries.len()))
+}
+
+/// Searches every position in the EPD file at `path` to `depth`, once with the full evaluation and once with material only,
+/// then prints how many positions each solved, and how many nodes each took
+///
+/// This measures what the positional weights are worth to the search, as a baseline for evaluation work.
+/// Both searches run on a single thread, so that the node counts are deterministic.
+pub fn compare_eval(path: &Path, depth: u8) -> anyhow::Result<()> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read test suite {}", path.display()))?;
+    let entries = parse(&contents)?;
+
+    let evaluators = [
+        ("full", EvalParams::DEFAULT),
+        ("material only", EvalParams::MATERIAL_ONLY),
+    ];
+    let mut solved = [0; 2];
+    let mut nodes = [0; 2];
+    for (i, entry) in entries.iter().enumerate() {
+        let name = entry.id.clone().unwrap_or_else(|| format!("#{}", i + 1));
+        let mut results = Vec::new();
+
+        for (j, (_, params)) in evaluators.iter().enumerate() {
+            let mut engine = Engine::with_config(EngineConfig {
+                threads: 1,
+                eval_params: params.clone(),
+                ..Default::default()
+            });
+            let mv = search_entry(
+                &mut engine,
+                entry,
+                UciSearchOptions {
+                    depth: Some(depth as u32),
+                    ..Default::default()
+                },
+            )?;
+
+            let passed = entry.passes(mv);
+            if passed {
+                solved[j] += 1;
+            }
+            nodes[j] += engine.nodes();
+            results.push(format!(
+                "{} {} ({} nodes)",
+                if passed { "PASS" } else { "FAIL" },
+                mv,
+                engine.nodes()
+            ));
+        }
+
+        println!("{}: {}", name, results.join(" vs "));
+    }
+
+    println!("Depth {} over {} positions:", depth, entries.len());
+    for (j, (evaluator, _)) in evaluators.iter().enumerate() {
+        println!(
+            "{:>13}: {}/{} solved, {} nodes",
+            evaluator,
+            solved[j],
+            entries.len(),
+            nodes[j]
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `moves` in UCI notation
+    fn moves(moves: &[&str]) -> Vec<ChessMove> {
+        moves
+            .iter()
+            .map(|mv| ChessMove::from_str(mv).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn operations_are_parsed_into_moves() {
+        // A capture with check, which needs the SAN suffix stripped
+        let entry: EpdEntry =
+            "r1bq2rk/pp3pbp/2p1p1pQ/7P/3P4/2PB1N2/PP3PPR/2KR4 w - - bm Qxh7+; id \"WAC.004\";"
+                .parse()
+                .unwrap();
+        assert_eq!(entry.id.as_deref(), Some("WAC.004"));
+        assert_eq!(entry.best_moves, moves(&["h6h7"]));
+        assert_eq!(entry.avoid_moves, moves(&[]));
+
+        // A move to avoid, with the id first
+        let entry: EpdEntry = "4k3/8/2p5/3p4/8/8/8/3QK3 w - - id \"defended pawn\"; am Qxd5;"
+            .parse()
+            .unwrap();
+        assert_eq!(entry.id.as_deref(), Some("defended pawn"));
+        assert_eq!(entry.best_moves, moves(&[]));
+        assert_eq!(entry.avoid_moves, moves(&["d1d5"]));
+    }
+
+    #[test]
+    fn failures_are_classified_by_a_deeper_search() {
+        let mut engine = Engine::default();
+
+        for (line, expected) in [
+            // Mate in two with either king move, but a shallow search grabs the pawn instead
+            (
+                "k7/8/2K5/7p/8/8/7R/8 w - - bm Kb6 Kc7; id \"depth failure\";",
+                FailureKind::Depth,
+            ),
+            // Taking the defended pawn loses the queen, however deeply it is searched
+            (
+                "4k3/8/2p5/3p4/8/8/8/3QK3 w - - bm Qxd5; id \"eval failure\";",
+                FailureKind::Eval,
+            ),
+        ] {
+            let entry: EpdEntry = line.parse().unwrap();
+            let options = UciSearchOptions {
+                depth: Some(1),
+                ..Default::default()
+            };
+            let mv = search_entry(&mut engine, &entry, options).unwrap();
+            assert!(!entry.passes(mv), "{} passed with {}", line, mv);
+
+            let (kind, deeper) = classify_failure(&mut engine, &entry, 4).unwrap();
+            assert_eq!(kind, expected, "{} with {}", line, deeper);
+        }
+    }
+}
@@ -1,2 +1,6 @@
+pub mod bench;
 pub mod engine;
+pub mod epd;
 pub mod score;
+pub mod selftest;
+pub mod uci_log;
@@ -1,60 +1,296 @@
-use std::{error::Error, io::stdin, str::FromStr};
+use std::{
+    error::Error,
+    io::stdin,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
 
-use chess::ChessMove;
+use chess::{Board, ChessMove};
+use clap::{Parser, Subcommand};
+use parking_lot::Mutex;
 use uci_parser::{UciCommand, UciResponse};
 
-use patch::engine::Engine;
+use patch::{
+    bench,
+    engine::{Engine, max_threads, perft::perft_divide},
+    epd, selftest, uci_log,
+};
+
+/// Default time to search each position of a `testsuite` for, in milliseconds
+const DEFAULT_TESTSUITE_MOVETIME_MS: u64 = 1000;
+
+/// Default depth to search each position of a `compare-eval` to
+const DEFAULT_COMPARE_EVAL_DEPTH: u8 = 5;
+
+/// UCI chess engine
+///
+/// Once started, commands are read from `stdin` as specified by the UCI protocol.
+/// The flags below pre-configure the engine, as a shortcut for the corresponding `setoption` commands.
+#[derive(Parser, Debug)]
+#[command(version)]
+struct Args {
+    /// Number of threads to search with (capped at the maximum set by `PATCH_THREADS`, if any)
+    #[arg(short, long)]
+    threads: Option<usize>,
+
+    /// Size of the transposition table, in megabytes
+    #[arg(long)]
+    hash: Option<usize>,
+
+    /// Run a quick battery of checks on the engine, then exit
+    #[arg(long)]
+    selftest: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+/// Commands that run instead of the UCI loop
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Search a fixed suite of positions, then print the total nodes (a signature of the search) and nodes per second
+    Bench {
+        /// Depth to search each position to
+        #[arg(default_value_t = bench::DEFAULT_DEPTH)]
+        depth: u32,
+    },
+    /// Search an EPD test suite to a fixed depth with the full evaluation, then with material only, and compare the results
+    CompareEval {
+        /// EPD file of the positions to search
+        #[arg(default_value = "fixtures/sample.epd")]
+        suite: PathBuf,
+
+        /// Depth to search each position to
+        #[arg(short, long, default_value_t = DEFAULT_COMPARE_EVAL_DEPTH)]
+        depth: u8,
+    },
+}
 
 fn main() -> Result<(), Box<dyn Error>> {
+    let args = Args::parse();
+
+    // Pin the size of the global thread pool, so that results are comparable across machines
+    // The engine searches in its own pool (see the `Threads` option), but this is used as a fallback
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(max_threads())
+        .build_global()?;
+
+    if args.selftest {
+        std::process::exit(if selftest::run() { 0 } else { 1 });
+    }
+    match args.command {
+        Some(Command::Bench { depth }) => {
+            bench::run(depth)?;
+            return Ok(());
+        }
+        Some(Command::CompareEval { suite, depth }) => {
+            epd::compare_eval(&suite, depth)?;
+            return Ok(());
+        }
+        None => {}
+    }
+
+    // The search runs on its own thread, so that we can keep reading commands (namely `stop`) while it runs.
+    // The search holds the engine until it finishes, so other commands that use the engine stop it first (see `finish_search`).
     let mut engine = Engine::default();
+    if let Some(threads) = args.threads {
+        engine.set_threads(threads);
+    }
+    if let Some(hash) = args.hash {
+        engine.set_hash_size(hash)?;
+    }
+    let stop = engine.stop_flag();
+    let pondering = engine.ponder_flag();
+    let engine = Arc::new(Mutex::new(engine));
+    let mut search: Option<JoinHandle<()>> = None;
 
     for line in stdin().lines() {
-        match line.unwrap().parse::<UciCommand>()? {
+        let line = line.unwrap();
+        uci_log::received(&line);
+
+        // `perft`, `testsuite`, and `bench` aren't part of UCI, so they are handled before the line is parsed as a UCI command
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("perft") => {
+                let depth = words.next().unwrap_or_default();
+                match depth.parse::<u8>() {
+                    Ok(depth) => {
+                        finish_search(&stop, &mut search);
+                        run_perft(engine.lock().board(), depth);
+                    }
+                    Err(e) => eprintln!("Invalid perft depth {:?}: {}", depth, e),
+                }
+                continue;
+            }
+            Some("testsuite") => {
+                // `testsuite <file> [movetime in ms] [depth to recheck failures at]`
+                let Some(path) = words.next() else {
+                    eprintln!("Expected a test suite file");
+                    continue;
+                };
+                let movetime = words
+                    .next()
+                    .map_or(Ok(DEFAULT_TESTSUITE_MOVETIME_MS), str::parse);
+                let recheck_depth = words.next().map(str::parse::<u8>).transpose();
+                match (movetime, recheck_depth) {
+                    (Ok(movetime), Ok(recheck_depth)) => {
+                        finish_search(&stop, &mut search);
+                        let suite = epd::run_suite(
+                            &mut engine.lock(),
+                            Path::new(path),
+                            Duration::from_millis(movetime),
+                            recheck_depth,
+                        );
+                        if let Err(e) = suite {
+                            eprintln!("{:#}", e);
+                        }
+                    }
+                    (Err(e), _) => eprintln!("Invalid test suite movetime: {}", e),
+                    (_, Err(e)) => eprintln!("Invalid test suite recheck depth: {}", e),
+                }
+                continue;
+            }
+            Some("bench") => {
+                let depth = words.next().map_or(Ok(bench::DEFAULT_DEPTH), str::parse);
+                match depth {
+                    Ok(depth) => {
+                        // The bench uses its own engines, but any search in progress would skew its timing
+                        finish_search(&stop, &mut search);
+                        if let Err(e) = bench::run(depth) {
+                            eprintln!("{}", e);
+                        }
+                    }
+                    Err(e) => eprintln!("Invalid bench depth: {}", e),
+                }
+                continue;
+            }
+            _ => {}
+        }
+
+        match line.parse::<UciCommand>()? {
             UciCommand::Uci => {
                 // Identify ourselves
-                println!("{}", UciResponse::Name("Patch"));
-                println!("{}", UciResponse::Author("sixfold"));
+                uci_log::send(UciResponse::Name("Patch"));
+                uci_log::send(UciResponse::Author("sixfold"));
+                // Advertise our options
+                for option in Engine::options() {
+                    uci_log::send(UciResponse::Option(option));
+                }
                 // Shake the nice GUI's hand
-                println!("{}", UciResponse::uciok());
+                uci_log::send(UciResponse::uciok());
+            }
+            UciCommand::Debug(debug) => {
+                finish_search(&stop, &mut search);
+                engine.lock().set_debug(debug);
             }
-            UciCommand::Debug(debug) => engine.set_debug(debug),
             UciCommand::IsReady => {
-                // Everything is blocking, so by the time we read this message, we're ready
-                // TODO: make it so that it's not all blocking
-                println!("{}", UciResponse::readyok());
+                // We're always able to take commands, even while searching, as none of them wait on the search indefinitely
+                uci_log::send(UciResponse::readyok());
+            }
+            UciCommand::SetOption { name, value } => {
+                // A bad option shouldn't take down the engine, so we just report it
+                finish_search(&stop, &mut search);
+                if let Err(e) = engine.lock().set_option(&name, value.as_deref()) {
+                    eprintln!("{}", e);
+                }
             }
-            UciCommand::SetOption { .. } => unimplemented!(),
             UciCommand::Register { .. } => {
                 // We don't perform registration, so this is a NOP
             }
             UciCommand::UciNewGame => {
-                engine.reset_game();
+                finish_search(&stop, &mut search);
+                engine.lock().reset_game();
             }
             UciCommand::Position { fen, moves } => {
+                // A bad move shouldn't take down the engine either, so we report it and keep the old position
                 let moves = moves
-                    .into_iter()
-                    .map(|s| ChessMove::from_str(&s).expect("Valid move"));
+                    .iter()
+                    .map(|s| {
+                        ChessMove::from_str(s).map_err(|e| format!("Invalid move {}: {}", s, e))
+                    })
+                    .collect::<Result<Vec<_>, _>>();
 
-                engine.set_position(fen.as_ref().map(|s| s.as_str()), moves)?;
+                finish_search(&stop, &mut search);
+                let result = moves.and_then(|moves| {
+                    engine
+                        .lock()
+                        .set_position(fen.as_deref(), moves.into_iter())
+                        .map_err(|e| e.to_string())
+                });
+                if let Err(e) = result {
+                    eprintln!("{}", e);
+                }
             }
             UciCommand::Go(options) => {
-                // The stop command isn't implemented, so we just block until we're done thinking
-                let mv = engine.search(options)?;
-                println!(
-                    "{}",
-                    UciResponse::BestMove {
-                        bestmove: Some(mv.to_string()),
-                        ponder: None,
+                // A previous search must be over before the flags are reset, or it would miss the `stop` meant for it
+                finish_search(&stop, &mut search);
+                // The flags are only ever set from this thread, so setting them here can't race with a `stop` or `ponderhit`
+                stop.store(false, Ordering::Relaxed);
+                pondering.store(options.ponder, Ordering::Relaxed);
+
+                let engine = Arc::clone(&engine);
+                search = Some(thread::spawn(move || {
+                    let mut engine = engine.lock();
+                    match engine.search(options) {
+                        Ok(mv) => uci_log::send(UciResponse::BestMove {
+                            bestmove: Some(engine.uci_move(engine.board(), mv)),
+                            ponder: engine.ponder_move().map(|ponder| {
+                                engine.uci_move(&engine.board().make_move_new(mv), ponder)
+                            }),
+                        }),
+                        Err(e) => eprintln!("{}", e),
                     }
-                );
+                }));
             }
             UciCommand::Stop => {
-                // NOP for now
+                stop.store(true, Ordering::Relaxed);
+            }
+            UciCommand::PonderHit => {
+                // The opponent played the move we were pondering on, so the search carries on as a normal one
+                pondering.store(false, Ordering::Relaxed);
+            }
+            UciCommand::Quit => {
+                finish_search(&stop, &mut search);
+                return Ok(());
             }
-            UciCommand::PonderHit => unimplemented!(),
-            UciCommand::Quit => return Ok(()),
         }
     }
 
     unreachable!()
 }
+
+/// Stops the search in progress (if any), and waits for it to send its best move
+///
+/// The search holds the engine's lock until it finishes, so this must be called before locking the engine from the main loop.
+/// Otherwise, an infinite search would block the loop, and the `stop` ending it would never be read.
+fn finish_search(stop: &AtomicBool, search: &mut Option<JoinHandle<()>>) {
+    if let Some(search) = search.take() {
+        stop.store(true, Ordering::Relaxed);
+        let _ = search.join();
+    }
+}
+
+/// Prints the [`perft_divide`] node counts of `board` at `depth`, followed by the total and the time taken
+fn run_perft(board: &Board, depth: u8) {
+    let start = Instant::now();
+    let divided = perft_divide(board, depth);
+    let elapsed = start.elapsed();
+
+    for (mv, nodes) in divided.iter() {
+        println!("{}: {}", mv, nodes);
+    }
+    let nodes: u64 = divided.iter().map(|(_, nodes)| nodes).sum();
+    println!();
+    println!("Nodes searched: {}", nodes);
+    println!(
+        "Time: {}ms ({} nps)",
+        elapsed.as_millis(),
+        nodes as u128 * 1000 / elapsed.as_millis().max(1)
+    );
+}
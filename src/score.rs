@@ -35,14 +35,44 @@ impl Score {
     ///
     /// In the case of mate scores, the mate counter is increased,
     /// as this is more convenient in the minimax algorithm.
+    ///
+    /// This is always used to bring a child's score up to its parent, one ply earlier.
+    /// So, flipping M0 (the child is checkmated) gives M1 (the parent delivers mate with its move),
+    /// and flipping M1 gives -M2 (the parent will be mated after two plies), and so on.
+    /// Flipping never produces M0, as a parent can never already be checkmated by its child.
+    ///
+    /// The mate counter saturates at [`i8::MAX`] plies, rather than overflowing on very long mating lines.
     pub fn flip(self) -> Self {
         match self {
             Score::Centipawns(cp) => Score::Centipawns(-cp),
             Score::Mate(m) => {
+                let plies = m.saturating_abs().saturating_add(1);
                 if m.is_positive() {
-                    Score::Mate((m.abs() + 1) * -1)
+                    Score::Mate(-plies)
                 } else {
-                    Score::Mate(m.abs() + 1)
+                    Score::Mate(plies)
+                }
+            }
+        }
+    }
+
+    /// Inverse of [`Self::flip`], used to convert a search bound into the child's perspective
+    ///
+    /// A child score `c` beats the bound `self` for the parent (`c.flip() > self`)
+    /// exactly when it falls below the unflipped bound for the child (`c < self.unflip()`).
+    /// Using [`Self::negate`] instead would be off by one ply for mate scores,
+    /// letting the child cut off on mates that are only as good as the bound.
+    ///
+    /// Bounds that no child score can reach from the parent's perspective (M0 and -M1)
+    /// unflip to [`Self::max`], so that they never cause a cutoff in the child.
+    pub fn unflip(self) -> Self {
+        match self {
+            Score::Centipawns(cp) => Score::Centipawns(-cp),
+            Score::Mate(m) => {
+                if m.is_positive() {
+                    Score::Mate(-(m - 1))
+                } else {
+                    Score::Mate((m.saturating_neg() - 1).max(1))
                 }
             }
         }
@@ -141,3 +171,41 @@ impl From<Score> for UciScore {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checkmate_flips_to_mate_in_one() {
+        assert_eq!(Score::Mate(0).flip(), Score::Mate(1));
+        assert_eq!(Score::Mate(1).flip(), Score::Mate(-2));
+        assert_eq!(Score::Mate(-2).flip(), Score::Mate(3));
+    }
+
+    #[test]
+    fn unflip_undoes_flip() {
+        for score in [
+            Score::Mate(0),
+            Score::Mate(1),
+            Score::Mate(-2),
+            Score::Mate(5),
+            Score::Mate(-6),
+            Score::cp(0),
+            Score::cp(123),
+            Score::cp(-45),
+        ] {
+            assert_eq!(score.flip().unflip(), score, "{:?}", score);
+        }
+    }
+
+    #[test]
+    fn long_mates_saturate_instead_of_overflowing() {
+        assert_eq!(Score::Mate(i8::MAX).flip(), Score::Mate(-i8::MAX));
+        assert_eq!(Score::Mate(-i8::MAX).flip(), Score::Mate(i8::MAX));
+        assert_eq!(Score::Mate(i8::MIN).flip(), Score::Mate(i8::MAX));
+        // Saturated mates still sort as the slowest mates there are
+        assert!(Score::Mate(i8::MAX) < Score::Mate(i8::MAX - 1));
+        assert!(Score::Mate(i8::MAX) > Score::cp(i16::MAX));
+    }
+}
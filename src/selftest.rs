@@ -0,0 +1,231 @@
+//! Quick end-to-end checks of the engine, run with `patch --selftest`
+//!
+//! Each check exercises a different part of the engine on a small, well-known position,
+//! and asserts a concrete expected result.
+//! The whole battery runs in about a second, and is meant as a sanity check that a build works.
+//! The thorough tests live beside the code they cover, and run with `cargo test`.
+
+use std::str::FromStr;
+
+use anyhow::{Context, bail, ensure};
+use chess::{Board, ChessMove};
+use uci_parser::UciSearchOptions;
+
+use crate::{
+    engine::{Engine, perft, see::see},
+    score::Score,
+};
+
+/// A single self-test check
+struct Check {
+    /// Name printed alongside the result
+    name: &'static str,
+    /// Runs the check, returning an error describing what went wrong if it fails
+    run: fn() -> anyhow::Result<()>,
+}
+
+/// All the checks run by [`run`]
+const CHECKS: [Check; 5] = [
+    Check {
+        name: "perft",
+        run: perft,
+    },
+    Check {
+        name: "mate distance",
+        run: mate_distance,
+    },
+    Check {
+        name: "knight fork",
+        run: knight_fork,
+    },
+    Check {
+        name: "static exchange",
+        run: static_exchange,
+    },
+    Check {
+        name: "threefold repetition",
+        run: threefold_repetition,
+    },
+];
+
+/// Runs every check, printing whether each one passed
+///
+/// Returns whether all the checks passed
+pub fn run() -> bool {
+    let mut passed = true;
+
+    for check in CHECKS.iter() {
+        match (check.run)() {
+            Ok(()) => println!("PASS {}", check.name),
+            Err(e) => {
+                println!("FAIL {}: {:#}", check.name, e);
+                passed = false;
+            }
+        }
+    }
+
+    passed
+}
+
+/// Positions with their known perft node counts at a given depth
+const PERFTS: [(&str, u8, u64); 2] = [
+    // The starting position
+    (
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        4,
+        197_281,
+    ),
+    // Kiwipete, which is full of castling, en passant, and promotion edge cases
+    (
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        3,
+        97_862,
+    ),
+];
+
+/// Move generation matches the known node counts
+fn perft() -> anyhow::Result<()> {
+    for (fen, depth, expected) in PERFTS {
+        let board = Board::from_str(fen).map_err(anyhow::Error::msg)?;
+        let nodes = perft::perft(&board, depth);
+        ensure!(
+            nodes == expected,
+            "expected {} nodes at depth {} on {}, got {}",
+            expected,
+            depth,
+            fen,
+            nodes
+        );
+
+        let divided: u64 = perft::perft_divide(&board, depth)
+            .iter()
+            .map(|(_, nodes)| nodes)
+            .sum();
+        ensure!(
+            divided == nodes,
+            "perft divide on {} adds up to {}, but perft counted {}",
+            fen,
+            divided,
+            nodes
+        );
+    }
+
+    Ok(())
+}
+
+/// Forced mates, with the number of plies until mate
+const MATES: [(&str, i8); 2] = [
+    // 1. Ra8#
+    ("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", 1),
+    // 1. Kc7 Ka7 2. Ra2#
+    ("k7/8/2K5/8/8/8/7R/8 w - - 0 1", 3),
+];
+
+/// The search finds forced mates, and reports the correct distance to mate from the root
+fn mate_distance() -> anyhow::Result<()> {
+    for (fen, plies) in MATES {
+        let (mv, score) = search(fen, 4)?;
+
+        ensure!(
+            score == Score::mate(plies),
+            "expected a mate in {} plies on {}, got {:?} ({})",
+            plies,
+            fen,
+            score,
+            mv
+        );
+    }
+
+    Ok(())
+}
+
+/// The search finds a simple tactic, forking the king and queen
+fn knight_fork() -> anyhow::Result<()> {
+    let expected = ChessMove::from_str("e4f6").context("Invalid expected move")?;
+    let (mv, score) = search("6k1/3q4/8/8/4N3/8/8/6K1 w - - 0 1", 4)?;
+
+    if mv != expected {
+        bail!("expected {}, got {} ({:?})", expected, mv, score);
+    }
+
+    Ok(())
+}
+
+/// Exchanges, with the material won by the capture once all the recaptures are played out
+const EXCHANGES: [(&str, &str, i16); 3] = [
+    // Pawn takes a knight defended by a pawn, and is taken back
+    ("4k3/8/2p5/3n4/4P3/8/8/4K3 w - - 0 1", "e4d5", 337 - 82),
+    // Queen takes a pawn defended by a pawn, and is taken back
+    ("4k3/8/2p5/3p4/8/8/8/3QK3 w - - 0 1", "d1d5", 82 - 1025),
+    // Rook takes an undefended rook
+    ("3r3k/8/8/8/8/8/8/3RK3 w - - 0 1", "d1d8", 477),
+];
+
+/// Static exchange evaluation scores simple exchanges correctly
+fn static_exchange() -> anyhow::Result<()> {
+    for (fen, mv, expected) in EXCHANGES {
+        let board = Board::from_str(fen).map_err(anyhow::Error::msg)?;
+        let mv = ChessMove::from_str(mv).context("Invalid move")?;
+        let value = see(&board, mv);
+
+        ensure!(
+            value == expected,
+            "expected {} to win {} on {}, got {}",
+            mv,
+            expected,
+            fen,
+            value
+        );
+    }
+
+    Ok(())
+}
+
+/// Repeating the starting position for the third time is scored as a draw
+fn threefold_repetition() -> anyhow::Result<()> {
+    // The knights move back and forth, and the last move repeats the position for the third time
+    let mut moves = [
+        "g1f3", "g8f6", "f3g1", "f6g8", "g1f3", "g8f6", "f3g1", "f6g8",
+    ]
+    .iter()
+    .map(|mv| ChessMove::from_str(mv))
+    .collect::<Result<Vec<_>, _>>()
+    .context("Invalid move")?;
+    let repeating = moves.pop().context("No repeating move")?;
+
+    let mut engine = Engine::default();
+    engine.set_position(None, moves.into_iter())?;
+    let ranked = engine.rank_moves(UciSearchOptions {
+        depth: Some(2),
+        infinite: true,
+        ..Default::default()
+    })?;
+    let score = ranked
+        .iter()
+        .find(|(mv, _)| *mv == repeating)
+        .map(|(_, score)| *score)
+        .context("Repeating move was not searched")?;
+
+    ensure!(
+        score == Score::cp(0),
+        "expected the repetition to be scored as a draw, got {:?}",
+        score
+    );
+
+    Ok(())
+}
+
+/// Searches `fen` to `depth` with a fresh engine, returning the best move and its score
+fn search(fen: &str, depth: u32) -> anyhow::Result<(ChessMove, Score)> {
+    let mut engine = Engine::default();
+    engine.set_position(Some(fen), std::iter::empty())?;
+
+    // The search is limited by depth alone, so that results don't depend on the speed of the machine
+    let result = engine.analyze(UciSearchOptions {
+        depth: Some(depth),
+        infinite: true,
+        ..Default::default()
+    })?;
+
+    Ok((result.best_move, result.score))
+}
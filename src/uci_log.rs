@@ -0,0 +1,61 @@
+//! Mirrors UCI traffic to a file, for diagnosing protocol issues with specific GUIs
+//!
+//! All responses must be sent through [`send`] (rather than printed directly), so that they end up in the log.
+
+use std::{
+    fmt::Display,
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use parking_lot::Mutex;
+
+/// The file that traffic is currently logged to, if any
+static LOG_FILE: Mutex<Option<File>> = Mutex::new(None);
+
+/// Starts logging to the file at `path` (appending if it already exists), or stops logging if `path` is `None`
+pub fn set_file(path: Option<&Path>) -> io::Result<()> {
+    let file = path
+        .map(|path| OpenOptions::new().create(true).append(true).open(path))
+        .transpose()?;
+
+    *LOG_FILE.lock() = file;
+    Ok(())
+}
+
+/// Logs a command received from the GUI
+pub fn received(line: &str) {
+    write(">>", line);
+}
+
+/// Sends a response to the GUI, logging it if enabled
+pub fn send(response: impl Display) {
+    let response = response.to_string();
+    println!("{}", response);
+    write("<<", &response);
+}
+
+/// Writes a timestamped `line` to the log file, if there is one
+///
+/// `direction` is `>>` for traffic going to the engine, and `<<` for traffic coming from it.
+/// Errors are ignored, as a broken log shouldn't take down the engine.
+fn write(direction: &str, line: &str) {
+    let mut file = LOG_FILE.lock();
+    let Some(file) = file.as_mut() else {
+        return;
+    };
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let _ = writeln!(
+        file,
+        "[{}.{:03}] {} {}",
+        timestamp.as_secs(),
+        timestamp.subsec_millis(),
+        direction,
+        line
+    );
+}
@@ -0,0 +1,44 @@
+//! Reproducibility of seeded self-play games (see [`Engine::set_seed`])
+
+use chess::ChessMove;
+use patch::engine::Engine;
+use uci_parser::UciSearchOptions;
+
+/// Moves of the opening that every game starts from
+const OPENING: [&str; 2] = ["e2e4", "e7e5"];
+
+/// Number of plies played in each game, after the opening
+const GAME_PLIES: usize = 6;
+
+/// Depth that each move is searched to
+const DEPTH: u32 = 4;
+
+/// Plays a short game of the engine against itself with `seed`, returning each move along with the nodes searched for it
+fn play(seed: u64) -> Vec<(ChessMove, u64)> {
+    let mut engine = Engine::default();
+    engine.set_seed(seed);
+    // The search is only deterministic with a single thread
+    engine.set_threads(1);
+
+    let mut moves: Vec<ChessMove> = OPENING.iter().map(|mv| mv.parse().unwrap()).collect();
+    let mut game = Vec::new();
+    for _ in 0..GAME_PLIES {
+        engine.set_position(None, moves.iter().copied()).unwrap();
+        let mv = engine
+            .search(UciSearchOptions {
+                depth: Some(DEPTH),
+                ..Default::default()
+            })
+            .unwrap();
+
+        game.push((mv, engine.nodes()));
+        moves.push(mv);
+    }
+
+    game
+}
+
+#[test]
+fn same_seed_same_game() {
+    assert_eq!(play(7), play(7));
+}
@@ -0,0 +1,301 @@
+//! End to end checks of the UCI protocol, driving the engine executable over `stdin`
+
+use std::{
+    io::{BufRead, BufReader, Write},
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use patch::engine::THREADS_ENV_VAR;
+
+/// How long to wait for each expected response, before deciding that the engine is stuck
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A UCI session with the engine executable
+///
+/// The engine's output is read on its own thread, so that a hung engine fails the test instead of hanging it.
+/// The engine is killed when the session is dropped.
+struct UciSession {
+    /// The engine process
+    child: Child,
+    /// The engine's `stdin`, which commands are written to
+    stdin: ChildStdin,
+    /// Lines printed by the engine, in order
+    lines: Receiver<String>,
+}
+
+impl UciSession {
+    /// Starts the engine with the command line `args` and environment variables `envs`
+    fn start(args: &[&str], envs: &[(&str, &str)]) -> Self {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_patch"))
+            .args(args)
+            .envs(envs.iter().copied())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("Failed to start the engine");
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Self {
+            child,
+            stdin,
+            lines,
+        }
+    }
+
+    /// Sends a single command to the engine
+    fn send(&mut self, command: &str) {
+        writeln!(self.stdin, "{}", command).unwrap();
+        self.stdin.flush().unwrap();
+    }
+
+    /// Waits for a line starting with `prefix`, returning every line up to and including it
+    fn expect(&self, prefix: &str) -> Vec<String> {
+        let deadline = Instant::now() + RESPONSE_TIMEOUT;
+        let mut lines = Vec::new();
+
+        loop {
+            match self
+                .lines
+                .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+            {
+                Ok(line) => {
+                    let found = line.starts_with(prefix);
+                    lines.push(line);
+                    if found {
+                        return lines;
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    panic!("timed out waiting for {:?}, got {:?}", prefix, lines)
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    panic!(
+                        "engine exited while waiting for {:?}, got {:?}",
+                        prefix, lines
+                    )
+                }
+            }
+        }
+    }
+}
+
+impl Drop for UciSession {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// The value following `name` in `line`, if any
+fn field<'a>(line: &'a str, name: &str) -> Option<&'a str> {
+    line.split_whitespace()
+        .skip_while(|word| *word != name)
+        .nth(1)
+}
+
+/// Number of `bestmove` responses in `lines`
+fn count_best_moves(lines: &[String]) -> usize {
+    lines
+        .iter()
+        .filter(|line| line.starts_with("bestmove"))
+        .count()
+}
+
+#[test]
+fn threads_env_caps_the_threads_option() {
+    let mut session = UciSession::start(&[], &[(THREADS_ENV_VAR, "1")]);
+    session.send("uci");
+    let lines = session.expect("uciok");
+
+    let threads = lines
+        .iter()
+        .find(|line| line.starts_with("option name Threads "))
+        .expect("Threads option not listed");
+    assert_eq!(field(threads, "max"), Some("1"), "{}", threads);
+}
+
+#[test]
+fn each_go_gets_one_bestmove() {
+    let mut session = UciSession::start(&[], &[]);
+    session.send("position startpos");
+
+    for _ in 0..2 {
+        session.send("go depth 2");
+        let lines = session.expect("bestmove");
+        assert_eq!(count_best_moves(&lines), 1, "{:?}", lines);
+    }
+
+    session.send("isready");
+    let lines = session.expect("readyok");
+    assert_eq!(count_best_moves(&lines), 0, "{:?}", lines);
+}
+
+#[test]
+fn hash_flag_sizes_the_table_before_any_command() {
+    // The same search fills a bigger table less
+    let hashfull = |megabytes: &str| -> u32 {
+        let mut session = UciSession::start(&["--hash", megabytes], &[]);
+        session.send("position startpos");
+        session.send("go depth 6");
+        let lines = session.expect("bestmove");
+
+        lines
+            .iter()
+            .rev()
+            .find_map(|line| field(line, "hashfull")?.parse().ok())
+            .unwrap_or_else(|| panic!("No hashfull reported with --hash {}", megabytes))
+    };
+
+    let (small, large) = (hashfull("1"), hashfull("64"));
+    assert!(large < small, "{} >= {}", large, small);
+}
+
+#[test]
+fn currmove_is_only_reported_on_long_searches() {
+    let mut session = UciSession::start(&[], &[]);
+    session.send("position startpos");
+    session.send("go depth 1");
+    let lines = session.expect("bestmove");
+    assert!(
+        lines.iter().all(|line| field(line, "currmove").is_none()),
+        "{:?}",
+        lines
+    );
+
+    session.send("go movetime 2000");
+    let lines = session.expect("bestmove");
+    let current = lines
+        .iter()
+        .find(|line| field(line, "currmove").is_some())
+        .unwrap_or_else(|| panic!("No currmove reported: {:?}", lines));
+    let number: usize = field(current, "currmovenumber").unwrap().parse().unwrap();
+    // There are 20 moves in the starting position
+    assert!((1..=20).contains(&number), "{}", current);
+}
+
+#[test]
+fn info_strings_are_only_sent_in_debug_mode() {
+    let count_info_strings = |lines: &[String]| {
+        lines
+            .iter()
+            .filter(|line| line.starts_with("info string"))
+            .count()
+    };
+
+    let mut session = UciSession::start(&[], &[]);
+    session.send("position startpos");
+    session.send("go movetime 50");
+    let lines = session.expect("bestmove");
+    assert_eq!(count_info_strings(&lines), 0, "{:?}", lines);
+
+    session.send("debug on");
+    session.send("go movetime 50");
+    let lines = session.expect("bestmove");
+    assert!(count_info_strings(&lines) > 0, "{:?}", lines);
+}
+
+#[test]
+fn ponder_time_is_not_counted_against_the_movetime() {
+    let mut session = UciSession::start(&[], &[]);
+    session.send("setoption name Ponder value true");
+    session.send("position startpos");
+
+    // Ponder for longer than the whole movetime
+    session.send("go ponder movetime 500");
+    thread::sleep(Duration::from_millis(700));
+    session.send("isready");
+    let lines = session.expect("readyok");
+    assert_eq!(count_best_moves(&lines), 0, "{:?}", lines);
+
+    let ponderhit = Instant::now();
+    session.send("ponderhit");
+    session.expect("bestmove");
+    // The movetime, less the margins kept for sending the move, and some leeway
+    let elapsed = ponderhit.elapsed();
+    assert!(elapsed >= Duration::from_millis(300), "{:?}", elapsed);
+}
+
+#[test]
+fn searches_end_when_other_commands_arrive() {
+    let mut session = UciSession::start(&[], &[]);
+    session.send("position startpos");
+
+    session.send("go infinite");
+    session.send("isready");
+    let lines = session.expect("readyok");
+    assert_eq!(count_best_moves(&lines), 0, "{:?}", lines);
+    session.send("stop");
+    session.expect("bestmove");
+
+    // Commands that need the engine end the search, rather than waiting for it forever
+    session.send("go infinite");
+    session.send("setoption name Contempt value 10");
+    session.send("position startpos moves e2e4");
+    session.send("isready");
+    let lines = session.expect("readyok");
+    assert_eq!(count_best_moves(&lines), 1, "{:?}", lines);
+
+    // A `go` right after a `stop` must not revive the old search, or reset its flag before it is seen
+    session.send("go infinite");
+    session.send("stop");
+    session.send("go depth 2");
+    session.expect("bestmove");
+    session.expect("bestmove");
+    session.send("isready");
+    let lines = session.expect("readyok");
+    assert_eq!(count_best_moves(&lines), 0, "{:?}", lines);
+}
+
+#[test]
+fn a_mate_found_while_pondering_is_reported_after_the_ponderhit() {
+    let mut session = UciSession::start(&[], &[]);
+    session.send("setoption name Ponder value true");
+    // Black's only move is Kg8, after which Rb8 is mate
+    session.send("position fen 7k/R7/8/8/8/8/8/1R2K3 b - - 0 1");
+
+    session.send("go ponder depth 3");
+    session.send("isready");
+    let lines = session.expect("readyok");
+    assert_eq!(count_best_moves(&lines), 0, "{:?}", lines);
+
+    session.send("ponderhit");
+    let lines = session.expect("bestmove");
+    assert!(
+        lines
+            .iter()
+            .any(|line| line.starts_with("info") && line.contains("score mate -1")),
+        "{:?}",
+        lines
+    );
+    assert_eq!(field(lines.last().unwrap(), "bestmove"), Some("h8g8"));
+}
+
+#[test]
+fn candidates_reports_that_many_lines_per_depth() {
+    let mut session = UciSession::start(&[], &[]);
+    session.send("setoption name Candidates value 3");
+    session.send("position startpos");
+    session.send("go depth 3");
+    let lines = session.expect("bestmove");
+
+    let reported: Vec<_> = lines
+        .iter()
+        .filter(|line| line.starts_with("info") && field(line, "depth") == Some("3"))
+        .filter_map(|line| field(line, "multipv"))
+        .collect();
+    assert_eq!(reported, ["1", "2", "3"], "{:?}", lines);
+}
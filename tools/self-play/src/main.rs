@@ -1,11 +1,59 @@
-use std::process::{Child, Command};
+use std::{
+    io::{self, BufRead, BufReader},
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+};
 
-use anyhow::{Context, bail};
-use clap::Parser;
+use anyhow::{Context, bail, ensure};
+use clap::{Parser, ValueEnum};
+use restore::{GitRestore, run_git};
+use results::{MatchRecord, MatchSummary};
+
+mod restore;
+mod results;
 
 /// Name for the git stash to put any uncommited changes into
 const GIT_STASH_NAME: &str = "__internal__ self play stash";
 
+/// Help text for when cutechess-cli can't be found
+const CUTECHESS_NOT_FOUND_HELP: &str = "cutechess-cli is required to run self-play matches. \
+Install it from https://github.com/cutechess/cutechess/releases (or your package manager), \
+and either add it to your PATH, or pass its location with --cutechess <path>";
+
+/// Opening book used when none is given, relative to the root of the repository
+const DEFAULT_BOOK: &str = "tools/self-play/books/balanced.epd";
+
+/// Number of plies played from each opening in the book
+const BOOK_PLIES: u8 = 16;
+
+/// Formats of opening books that cutechess-cli can read
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum BookFormat {
+    /// One position per line
+    Epd,
+    /// Games, whose moves are played up to the book depth
+    Pgn,
+}
+
+impl BookFormat {
+    /// Guesses the format of the book at `path` from its extension
+    fn from_extension(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "epd" => Some(Self::Epd),
+            "pgn" => Some(Self::Pgn),
+            _ => None,
+        }
+    }
+
+    /// Name of the format in cutechess-cli's arguments
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Epd => "epd",
+            Self::Pgn => "pgn",
+        }
+    }
+}
+
 /// Utility to set up the engine to play against an older version of itself
 #[derive(Parser, Debug)]
 struct Args {
@@ -24,11 +72,48 @@ struct Args {
     /// Elo 1 to use for the SPRT test
     #[arg(short = 'a', long)]
     elo1: f32,
+
+    /// JSON-lines file to append a record of the completed match to
+    #[arg(short, long)]
+    results: Option<PathBuf>,
+
+    /// Path to the cutechess-cli executable, if it is not on the `PATH`
+    #[arg(long)]
+    cutechess: Option<PathBuf>,
+
+    /// PGN file to save the played games to, for reviewing them afterwards
+    #[arg(long)]
+    pgn: Option<PathBuf>,
+
+    /// Git ref (branch, tag, or commit) of the version to test against
+    #[arg(short, long, default_value = "master")]
+    baseline: String,
+
+    /// Opening book to start the games from
+    ///
+    /// Each opening is played twice, once with each engine as white, so that games aren't all near-duplicates
+    #[arg(long, default_value = DEFAULT_BOOK)]
+    book: PathBuf,
+
+    /// Format of the opening book, if it can't be guessed from its extension
+    #[arg(long, value_enum)]
+    book_format: Option<BookFormat>,
 }
 
 fn main() -> anyhow::Result<()> {
     let args = Args::parse();
 
+    // Make sure we can actually run the test before spending time on builds
+    let ext = if cfg!(windows) { ".exe" } else { "" };
+    let cutechess = args
+        .cutechess
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(format!("cutechess-cli{}", ext)));
+    check_cutechess(&cutechess)?;
+    let book_format = check_book(&args.book, args.book_format)?;
+    check_baseline(&args.baseline)?;
+    let baseline_name = engine_name(&args.baseline);
+
     // Build current rev
     println!("Building experimental");
     let experimental_rev = get_rev();
@@ -47,73 +132,133 @@ fn main() -> anyhow::Result<()> {
         .stdout;
     let output = String::from_utf8(output).expect("Failed to parse command output from utf-8");
     let stashed = output != "No local changes to save\n";
+    let mut git_restore = GitRestore::new(stashed, run_git);
 
-    // Swap to master
-    println!("Switching to master");
-    let child = Command::new("git").args(["checkout", "master"]).spawn();
-    drive_spawned_child(child, "checkout master", true)?;
+    // Swap to the baseline
+    println!("Switching to {}", args.baseline);
+    let child = Command::new("git")
+        .args(["checkout", &args.baseline])
+        .spawn();
+    drive_spawned_child(child, &format!("checkout {}", args.baseline), true)?;
+    git_restore.switched();
 
-    // Build master
-    println!("Building master");
-    let maseter_rev = get_rev();
+    // Build the baseline
+    println!("Building {}", args.baseline);
+    let baseline_rev = get_rev();
     let child = Command::new("cargo")
         .args(["build", "--release"])
-        .env("CARGO_TARGET_DIR", "./target/master")
+        .env("CARGO_TARGET_DIR", "./target/baseline")
         .spawn();
-    drive_spawned_child(child, "master build", true)?;
+    drive_spawned_child(child, "baseline build", true)?;
 
     // Reset git state
     // Note: Since all the build artifacts end up in the gitignore'd target folder,
     // once they are built, we can freely mess with git however we wish.
     // We reset git before running the test for convenience, as tests can take a long time.
-    println!("Switching back to experimental branch");
-    let child = Command::new("git").args(["switch", "-"]).spawn();
-    drive_spawned_child(child, "git switch", true)?;
-
-    if stashed {
-        println!("Applying previously stashed changes");
-        let child = Command::new("git").args(["stash", "pop"]).spawn();
-        drive_spawned_child(child, "git stash pop", false)?; // Ignore exit code, as this can fail if there is no stash
-    }
+    // If anything above failed, this is done when `git_restore` is dropped instead
+    git_restore.restore()?;
 
     // Test
-    let ext = if cfg!(windows) { ".exe" } else { "" };
-
     println!(
-        "Testing {} (experimental) against {} (master)",
-        &experimental_rev, &maseter_rev
+        "Testing {} (experimental) against {} ({})",
+        &experimental_rev, &baseline_rev, args.baseline
     );
-    let child = Command::new(format!("cutechess-cli{}", ext))
-        .args([
-            "-engine",
-            &format!("cmd=./target/experimental/release/patch{}", ext),
-            "name=patch-experimental",
-            "stderr=./experimental_stderr.txt",
-            "-engine",
-            &format!("cmd=./target/master/release/patch{}", ext),
-            "name=patch-master",
-            "stderr=./master_stderr.txt",
-            "-concurrency",
-            &args.concurrency.to_string(),
-            "-recover",
-            "-each",
-            "proto=uci",
-            &format!("tc={}", args.tc),
-            "timemargin=500",
-            "-rounds",
-            "10000",
-            "-sprt",
-            &format!("elo0={}", args.elo0),
-            &format!("elo1={}", args.elo1),
-            "alpha=0.05",
-            "beta=0.05",
-        ])
+    let mut cutechess_args = vec![
+        "-engine".to_string(),
+        format!("cmd=./target/experimental/release/patch{}", ext),
+        "name=patch-experimental".to_string(),
+        "stderr=./experimental_stderr.txt".to_string(),
+        "-engine".to_string(),
+        format!("cmd=./target/baseline/release/patch{}", ext),
+        format!("name={}", baseline_name),
+        "stderr=./baseline_stderr.txt".to_string(),
+        "-concurrency".to_string(),
+        args.concurrency.to_string(),
+        "-recover".to_string(),
+        "-each".to_string(),
+        "proto=uci".to_string(),
+        format!("tc={}", args.tc),
+        "timemargin=500".to_string(),
+        "-rounds".to_string(),
+        "10000".to_string(),
+        "-sprt".to_string(),
+        format!("elo0={}", args.elo0),
+        format!("elo1={}", args.elo1),
+        "alpha=0.05".to_string(),
+        "beta=0.05".to_string(),
+        "-openings".to_string(),
+        format!("file={}", args.book.display()),
+        format!("format={}", book_format.as_str()),
+        "order=random".to_string(),
+        format!("plies={}", BOOK_PLIES),
+        "-games".to_string(),
+        "2".to_string(),
+        "-repeat".to_string(),
+    ];
+    if let Some(pgn) = &args.pgn {
+        cutechess_args.extend(["-pgnout".to_string(), pgn.display().to_string()]);
+    }
+    let child = Command::new(&cutechess)
+        .args(&cutechess_args)
+        .stdout(Stdio::piped())
         .spawn();
-    drive_spawned_child(child, "cutechess", true)?;
+    let summary = drive_cutechess(child, &cutechess)?;
+    println!("Final result: {}", summary);
+    if let Some(pgn) = &args.pgn {
+        println!("Saved games to {}", pgn.display());
+    }
+
+    if let Some(path) = args.results {
+        let record = MatchRecord::now(
+            experimental_rev,
+            baseline_rev,
+            args.tc,
+            args.elo0,
+            args.elo1,
+            summary,
+        );
+        record.append_to(&path)?;
+        println!("Appended match record to {}", path.display());
+    }
 
     Ok(())
 }
 
+/// Checks that the git ref `baseline` resolves to a commit, so that we fail before spending time on builds
+fn check_baseline(baseline: &str) -> anyhow::Result<()> {
+    let status = Command::new("git")
+        .args(["rev-parse", "--verify", "--quiet"])
+        .arg(format!("{}^{{commit}}", baseline))
+        .stdout(Stdio::null())
+        .status()
+        .context("Failed to start command: git rev-parse")?;
+
+    ensure!(
+        status.success(),
+        "Could not find a commit for the baseline {:?}. Pass a branch, tag, or commit hash with --baseline",
+        baseline
+    );
+    Ok(())
+}
+
+/// Name that the engine built from the git ref `reference` is given in cutechess-cli
+///
+/// Refs can contain characters that cutechess-cli (and the PGN tags it writes) don't like, like slashes, so those are replaced
+fn engine_name(reference: &str) -> String {
+    let reference: String = reference
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-') {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect();
+
+    format!("patch-{}", reference)
+}
+
 fn get_rev() -> String {
     let output = Command::new("git")
         .args(["rev-parse", "--short", "HEAD"])
@@ -126,6 +271,76 @@ fn get_rev() -> String {
     output
 }
 
+/// Checks that cutechess-cli can be started from `path`,
+/// returning an actionable error if it is missing
+fn check_cutechess(path: &Path) -> anyhow::Result<()> {
+    Command::new(path)
+        .arg("--version")
+        .output()
+        .map_err(|e| cutechess_spawn_error(e, path))?;
+
+    Ok(())
+}
+
+/// Checks that the opening book at `path` exists, returning its format
+///
+/// The format is `format` if given, or guessed from the book's extension otherwise
+fn check_book(path: &Path, format: Option<BookFormat>) -> anyhow::Result<BookFormat> {
+    ensure!(
+        path.is_file(),
+        "Could not find the opening book at {}",
+        path.display()
+    );
+
+    format
+        .or_else(|| BookFormat::from_extension(path))
+        .with_context(|| {
+            format!(
+                "Could not tell the format of the opening book at {}, pass it with --book-format",
+                path.display()
+            )
+        })
+}
+
+/// Converts an error from spawning cutechess-cli into a friendly error
+///
+/// A missing executable is common on the first run, so it gets a dedicated message explaining how to fix it
+fn cutechess_spawn_error(error: io::Error, path: &Path) -> anyhow::Error {
+    if error.kind() == io::ErrorKind::NotFound {
+        anyhow::anyhow!(
+            "Could not find cutechess-cli at {}\n{}",
+            path.display(),
+            CUTECHESS_NOT_FOUND_HELP
+        )
+    } else {
+        anyhow::Error::new(error).context("Failed to start command: cutechess")
+    }
+}
+
+/// Waits for a spawned cutechess-cli process to finish,
+/// echoing its output while summarizing the match results
+fn drive_cutechess(child: io::Result<Child>, path: &Path) -> anyhow::Result<MatchSummary> {
+    let mut child = child.map_err(|e| cutechess_spawn_error(e, path))?;
+    let mut summary = MatchSummary::default();
+
+    let stdout = child
+        .stdout
+        .take()
+        .context("Failed to capture output: cutechess")?;
+    for line in BufReader::new(stdout).lines() {
+        let line = line.context("Failed to read output: cutechess")?;
+        println!("{}", line);
+        summary.update(&line);
+    }
+
+    let exit = child.wait().context("Command is not running: cutechess")?;
+    if !exit.success() {
+        bail!("Command failed: cutechess");
+    }
+
+    Ok(summary)
+}
+
 /// Waits for a spawned child process to finish, spitting out errors if anything goes wrong
 ///
 /// Inputs:
@@ -148,3 +363,78 @@ fn drive_spawned_child(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_cutechess() {
+        let path = Path::new("./does-not-exist/cutechess-cli");
+        let error = check_cutechess(path).unwrap_err().to_string();
+
+        assert!(
+            error.starts_with("Could not find cutechess-cli at"),
+            "{}",
+            error
+        );
+        assert!(error.contains("--cutechess <path>"), "{}", error);
+    }
+    #[test]
+    fn book_format_from_extension() {
+        assert_eq!(
+            BookFormat::from_extension(Path::new("books/openings.EPD")),
+            Some(BookFormat::Epd)
+        );
+        assert_eq!(
+            BookFormat::from_extension(Path::new("openings.pgn")),
+            Some(BookFormat::Pgn)
+        );
+        assert_eq!(BookFormat::from_extension(Path::new("openings.txt")), None);
+        assert_eq!(BookFormat::from_extension(Path::new("openings")), None);
+    }
+
+    #[test]
+    fn book_checks() {
+        // Tests run from the package's directory, rather than the root of the repository
+        let book = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../..")
+            .join(DEFAULT_BOOK);
+        assert_eq!(check_book(&book, None).unwrap(), BookFormat::Epd);
+        assert_eq!(
+            check_book(&book, Some(BookFormat::Pgn)).unwrap(),
+            BookFormat::Pgn
+        );
+
+        let error = check_book(Path::new("missing.epd"), None).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Could not find the opening book at missing.epd"
+        );
+
+        // The manifest exists, but isn't an opening book by its extension
+        let manifest = Path::new(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml");
+        let error = check_book(&manifest, None).unwrap_err();
+        assert!(error.to_string().contains("--book-format"), "{}", error);
+        assert_eq!(
+            check_book(&manifest, Some(BookFormat::Epd)).unwrap(),
+            BookFormat::Epd
+        );
+    }
+    #[test]
+    fn engine_names() {
+        assert_eq!(engine_name("master"), "patch-master");
+        assert_eq!(engine_name("v1.2_rc-1"), "patch-v1.2_rc-1");
+        assert_eq!(engine_name("feature/new eval"), "patch-feature-new-eval");
+        assert_eq!(engine_name("HEAD~2"), "patch-HEAD-2");
+    }
+
+    #[test]
+    fn baseline_checks() {
+        // Tests run inside the repository, so its history is there to resolve refs against
+        check_baseline("HEAD").unwrap();
+
+        let error = check_baseline("no-such-branch-for-self-play").unwrap_err();
+        assert!(error.to_string().contains("--baseline"), "{}", error);
+    }
+}
@@ -0,0 +1,163 @@
+//! Putting the repository back the way the user left it, see [`GitRestore`]
+
+use std::process::Command;
+
+use anyhow::Context;
+
+use crate::drive_spawned_child;
+
+/// Runs git with `args`, failing if it can't be started or exits with an error
+pub fn run_git(args: &[&str]) -> anyhow::Result<()> {
+    let child = Command::new("git").args(args).spawn();
+    drive_spawned_child(child, &format!("git {}", args.join(" ")), true)
+}
+
+/// Guard that undoes the git changes made to build the baseline: switching away from the user's branch, and stashing their changes
+///
+/// The changes are undone when the guard is dropped, so this also happens if a build fails or the tool panics partway through.
+/// Call [`GitRestore::restore`] to undo them early, and find out whether that worked.
+///
+/// Git is run through `G` (normally [`run_git`]), so that failures can be simulated.
+pub struct GitRestore<G: FnMut(&[&str]) -> anyhow::Result<()>> {
+    /// Runs git with the given arguments
+    git: G,
+    /// Whether we switched away from the user's branch
+    switched: bool,
+    /// Whether the user's uncommitted changes are stashed
+    stashed: bool,
+}
+
+impl<G: FnMut(&[&str]) -> anyhow::Result<()>> GitRestore<G> {
+    /// Creates a guard, where the user's changes were just stashed (if `stashed`)
+    pub fn new(stashed: bool, git: G) -> Self {
+        Self {
+            git,
+            switched: false,
+            stashed,
+        }
+    }
+
+    /// Records that we switched away from the user's branch
+    pub fn switched(&mut self) {
+        self.switched = true;
+    }
+
+    /// Switches back to the user's branch and applies their stashed changes, in that order
+    pub fn restore(mut self) -> anyhow::Result<()> {
+        self.undo()
+    }
+
+    /// Undoes whatever has been done so far, so that it is safe to call again if it fails partway through
+    fn undo(&mut self) -> anyhow::Result<()> {
+        if self.switched {
+            println!("Switching back to experimental branch");
+            (self.git)(&["switch", "-"])?;
+            self.switched = false;
+        }
+
+        if self.stashed {
+            println!("Applying previously stashed changes");
+            // A failed pop (e.g. on conflicts) leaves the stash in place, and trying again won't help, so it is left to the user
+            self.stashed = false;
+            (self.git)(&["stash", "pop"]).context(
+                "Uncommitted changes are still stashed: run `git stash pop` to get them back",
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<G: FnMut(&[&str]) -> anyhow::Result<()>> Drop for GitRestore<G> {
+    fn drop(&mut self) {
+        if !self.switched && !self.stashed {
+            return;
+        }
+
+        println!("Restoring git state after a failure");
+        match self.undo() {
+            Ok(()) => println!(
+                "Restored git state: back on the experimental branch, with any uncommitted changes"
+            ),
+            Err(e) => {
+                eprintln!("Failed to restore git state: {:#}", e);
+                if self.switched {
+                    eprintln!("Run `git switch -` to get back to the experimental branch");
+                }
+                if self.stashed {
+                    eprintln!("Run `git stash pop` to get back any uncommitted changes");
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use anyhow::bail;
+
+    use super::*;
+
+    /// A git runner that records every command, and fails the ones starting with `failing`
+    fn fake_git(
+        failing: &'static str,
+    ) -> (
+        Rc<RefCell<Vec<String>>>,
+        impl FnMut(&[&str]) -> anyhow::Result<()>,
+    ) {
+        let calls = Rc::new(RefCell::new(Vec::new()));
+        let recorded = Rc::clone(&calls);
+        let git = move |args: &[&str]| {
+            let command = args.join(" ");
+            recorded.borrow_mut().push(command.clone());
+            if command.starts_with(failing) {
+                bail!("Command failed: git {}", command);
+            }
+            Ok(())
+        };
+
+        (calls, git)
+    }
+
+    #[test]
+    fn restore_switches_back_then_pops() {
+        let (calls, git) = fake_git("nothing");
+        let mut restore = GitRestore::new(true, git);
+        restore.switched();
+
+        restore.restore().unwrap();
+        assert_eq!(*calls.borrow(), ["switch -", "stash pop"]);
+    }
+
+    #[test]
+    fn failed_stash_pop_is_reported() {
+        let (calls, git) = fake_git("stash pop");
+        let mut restore = GitRestore::new(true, git);
+        restore.switched();
+
+        let error = format!("{:#}", restore.restore().unwrap_err());
+        assert!(error.contains("run `git stash pop`"), "{}", error);
+        // The pop isn't retried when the guard is dropped
+        assert_eq!(*calls.borrow(), ["switch -", "stash pop"]);
+    }
+
+    #[test]
+    fn drop_restores_after_a_failure() {
+        let (calls, git) = fake_git("nothing");
+        let mut restore = GitRestore::new(true, git);
+        restore.switched();
+
+        drop(restore);
+        assert_eq!(*calls.borrow(), ["switch -", "stash pop"]);
+    }
+
+    #[test]
+    fn nothing_to_restore() {
+        let (calls, git) = fake_git("nothing");
+
+        GitRestore::new(false, git).restore().unwrap();
+        assert!(calls.borrow().is_empty());
+    }
+}
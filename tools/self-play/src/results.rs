@@ -0,0 +1,301 @@
+//! Parsing of cutechess-cli's match output, and the JSON-lines record of completed matches
+
+use std::{
+    fmt::{self, Display},
+    fs::OpenOptions,
+    io::Write,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Context;
+
+/// Running summary of a match, built up from cutechess-cli's output as it comes in
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct MatchSummary {
+    /// Games won by the experimental engine
+    pub wins: u32,
+    /// Games lost by the experimental engine
+    pub losses: u32,
+    /// Drawn games
+    pub draws: u32,
+    /// Elo difference estimate, and its error margin
+    pub elo: Option<(f32, f32)>,
+    /// Outcome of the SPRT, if it terminated
+    pub sprt: Option<SprtResult>,
+}
+
+/// Outcome of a terminated SPRT
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SprtResult {
+    /// The null hypothesis (elo0) was accepted
+    H0,
+    /// The alternative hypothesis (elo1) was accepted
+    H1,
+}
+
+impl MatchSummary {
+    /// Total number of games played
+    pub fn games(&self) -> u32 {
+        self.wins + self.losses + self.draws
+    }
+
+    /// Updates the summary from a single line of cutechess-cli output
+    ///
+    /// Lines that don't contain any match statistics are ignored.
+    /// The lines we care about look like:
+    /// - `Score of patch-experimental vs patch-master: 12 - 10 - 5  [0.537] 27`
+    /// - `Elo difference: 25.8 +/- 102.4, LOS: 69.1 %, DrawRatio: 18.5 %`
+    /// - `SPRT: llr 2.95 (100.0%), lbound -2.94, ubound 2.94 - H1 was accepted`
+    pub fn update(&mut self, line: &str) {
+        let line = line.trim();
+
+        if let Some(rest) = line.strip_prefix("Score of ") {
+            // Skip past the engine names
+            let Some((_, stats)) = rest.split_once(": ") else {
+                return;
+            };
+            let mut counts = stats
+                .split(" - ")
+                .map(|count| count.split_whitespace().next().and_then(|c| c.parse().ok()));
+
+            if let (Some(Some(wins)), Some(Some(losses)), Some(Some(draws))) =
+                (counts.next(), counts.next(), counts.next())
+            {
+                self.wins = wins;
+                self.losses = losses;
+                self.draws = draws;
+            }
+        } else if let Some(rest) = line.strip_prefix("Elo difference: ") {
+            let mut parts = rest.split(',').next().unwrap_or_default().split("+/-");
+
+            if let (Some(Ok(elo)), Some(Ok(margin))) = (
+                parts.next().map(|p| p.trim().parse()),
+                parts.next().map(|p| p.trim().parse()),
+            ) {
+                self.elo = Some((elo, margin));
+            }
+        } else if line.starts_with("SPRT:") {
+            if line.ends_with("H0 was accepted") {
+                self.sprt = Some(SprtResult::H0);
+            } else if line.ends_with("H1 was accepted") {
+                self.sprt = Some(SprtResult::H1);
+            }
+        }
+    }
+}
+
+impl Display for MatchSummary {
+    /// Formats the summary on one line, like `12 W / 10 L / 5 D, Elo 25.8 +/- 102.4, H1 accepted`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} W / {} L / {} D", self.wins, self.losses, self.draws)?;
+        if let Some((elo, margin)) = self.elo {
+            write!(f, ", Elo {:.1} +/- {:.1}", elo, margin)?;
+        }
+        match self.sprt {
+            Some(SprtResult::H0) => write!(f, ", H0 accepted"),
+            Some(SprtResult::H1) => write!(f, ", H1 accepted"),
+            None => write!(f, ", SPRT inconclusive"),
+        }
+    }
+}
+
+/// Record of a completed match, written as a single line of JSON
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchRecord {
+    /// Unix timestamp (in seconds) of when the match finished
+    pub date: u64,
+    /// Git revision of the experimental engine
+    pub experimental_rev: String,
+    /// Git revision of the baseline engine
+    pub baseline_rev: String,
+    /// Time control used
+    pub tc: String,
+    /// Elo 0 used for the SPRT test
+    pub elo0: f32,
+    /// Elo 1 used for the SPRT test
+    pub elo1: f32,
+    /// Final match statistics
+    pub summary: MatchSummary,
+}
+
+impl MatchRecord {
+    /// Creates a record of a match that just finished
+    pub fn now(
+        experimental_rev: String,
+        baseline_rev: String,
+        tc: String,
+        elo0: f32,
+        elo1: f32,
+        summary: MatchSummary,
+    ) -> Self {
+        let date = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+
+        Self {
+            date,
+            experimental_rev,
+            baseline_rev,
+            tc,
+            elo0,
+            elo1,
+            summary,
+        }
+    }
+
+    /// Serializes the record into a single line of JSON (without a trailing newline)
+    pub fn to_json(&self) -> String {
+        let result = match self.summary.sprt {
+            Some(SprtResult::H0) => "\"H0\"",
+            Some(SprtResult::H1) => "\"H1\"",
+            None => "null",
+        };
+        let (elo, elo_margin) = match self.summary.elo {
+            Some((elo, margin)) => (json_number(elo), json_number(margin)),
+            None => ("null".to_string(), "null".to_string()),
+        };
+
+        format!(
+            "{{\"date\":{},\"experimental_rev\":{},\"baseline_rev\":{},\"tc\":{},\"elo0\":{},\"elo1\":{},\"result\":{},\"games\":{},\"wins\":{},\"losses\":{},\"draws\":{},\"elo\":{},\"elo_margin\":{}}}",
+            self.date,
+            json_string(&self.experimental_rev),
+            json_string(&self.baseline_rev),
+            json_string(&self.tc),
+            json_number(self.elo0),
+            json_number(self.elo1),
+            result,
+            self.summary.games(),
+            self.summary.wins,
+            self.summary.losses,
+            self.summary.draws,
+            elo,
+            elo_margin,
+        )
+    }
+
+    /// Appends the record to the JSON-lines file at `path`, creating it if needed
+    pub fn append_to(&self, path: &Path) -> anyhow::Result<()> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .context(format!("Failed to open results file: {}", path.display()))?;
+
+        writeln!(file, "{}", self.to_json()).context(format!(
+            "Failed to write to results file: {}",
+            path.display()
+        ))
+    }
+}
+
+/// Formats `n` as a JSON number
+///
+/// JSON has no infinities or NaN, which cutechess-cli reports for one-sided matches (e.g. `Elo difference: inf +/- nan`),
+/// so those are written as `null`
+fn json_number(n: f32) -> String {
+    if n.is_finite() {
+        n.to_string()
+    } else {
+        "null".to_string()
+    }
+}
+
+/// Quotes and escapes `s` as a JSON string
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A record of a match with the given Elo estimate
+    fn record(elo: Option<(f32, f32)>) -> MatchRecord {
+        MatchRecord {
+            date: 1_700_000_000,
+            experimental_rev: "abc1234".to_string(),
+            baseline_rev: "def5678".to_string(),
+            tc: "8+0.08".to_string(),
+            elo0: 0.0,
+            elo1: 5.0,
+            summary: MatchSummary {
+                wins: 3,
+                losses: 1,
+                draws: 2,
+                elo,
+                sprt: Some(SprtResult::H1),
+            },
+        }
+    }
+
+    #[test]
+    fn to_json() {
+        assert_eq!(
+            record(Some((25.5, 102.25))).to_json(),
+            "{\"date\":1700000000,\"experimental_rev\":\"abc1234\",\"baseline_rev\":\"def5678\",\"tc\":\"8+0.08\",\"elo0\":0,\"elo1\":5,\"result\":\"H1\",\"games\":6,\"wins\":3,\"losses\":1,\"draws\":2,\"elo\":25.5,\"elo_margin\":102.25}"
+        );
+    }
+
+    #[test]
+    fn to_json_non_finite_elo() {
+        let json = record(Some((f32::INFINITY, f32::NAN))).to_json();
+        assert!(
+            json.ends_with("\"elo\":null,\"elo_margin\":null}"),
+            "{}",
+            json
+        );
+    }
+
+    #[test]
+    fn json_string_escapes() {
+        assert_eq!(json_string("a\"b\\c\n"), "\"a\\\"b\\\\c\\u000a\"");
+    }
+
+    /// Tail of cutechess-cli's output from a finished match, with the statistics printed at each interval
+    const CUTECHESS_OUTPUT: &str = "\
+Started game 27 of 10000 (patch-experimental vs patch-master)
+Finished game 26 (patch-master vs patch-experimental): 1/2-1/2 {Draw by 3-fold repetition}
+Score of patch-experimental vs patch-master: 11 - 10 - 5  [0.519] 26
+...      patch-experimental playing White: 6 - 4 - 3  [0.577] 13
+Elo difference: 13.4 +/- 110.9, LOS: 59.5 %, DrawRatio: 19.2 %
+SPRT: llr 0.12 (4.1%), lbound -2.94, ubound 2.94
+Finished game 27 (patch-experimental vs patch-master): 1-0 {White mates}
+Score of patch-experimental vs patch-master: 12 - 10 - 5  [0.537] 27
+Elo difference: 25.8 +/- 102.4, LOS: 69.1 %, DrawRatio: 18.5 %
+SPRT: llr 2.95 (100.0%), lbound -2.94, ubound 2.94 - H1 was accepted
+Finished match
+";
+
+    #[test]
+    fn summary_from_cutechess_output() {
+        let mut summary = MatchSummary::default();
+        for line in CUTECHESS_OUTPUT.lines() {
+            summary.update(line);
+        }
+
+        assert_eq!(
+            summary,
+            MatchSummary {
+                wins: 12,
+                losses: 10,
+                draws: 5,
+                elo: Some((25.8, 102.4)),
+                sprt: Some(SprtResult::H1),
+            }
+        );
+        assert_eq!(summary.games(), 27);
+    }
+}
@@ -0,0 +1,146 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::{Context, ensure};
+use chess::{Board, Color};
+use clap::Parser;
+use patch::{
+    engine::evaluation::{EvalParams, eval_heuristic},
+    score::Score,
+};
+use tune::read_weights;
+
+/// Width of the longest bar in the histogram, in characters
+const MAX_BAR_WIDTH: usize = 40;
+
+/// Compares the evaluations of two sets of weights over a set of positions
+///
+/// Each set of weights is either a file written by `tune --output`, or `default` for the engine's own weights.
+/// The differences (new minus base, in centipawns from white's perspective) are summarized in a few lines,
+/// which can be pasted into a commit message to show whether a change shifts every position or only a few.
+#[derive(Parser, Debug)]
+struct Args {
+    /// Weights to compare against
+    base: String,
+
+    /// Weights to compare
+    new: String,
+
+    /// File of positions, one FEN per line (anything after a `;`, like a tuning sample's result, is ignored)
+    #[arg(short, long, default_value = "tools/tune/fixtures/sample.txt")]
+    positions: PathBuf,
+
+    /// Width of each bucket of the histogram, in centipawns
+    #[arg(short, long, default_value_t = 10)]
+    bucket: i32,
+}
+
+/// Loads the weights named by `arg`: `default`, or a weights file
+fn load_params(arg: &str) -> anyhow::Result<EvalParams> {
+    if arg == "default" {
+        Ok(EvalParams::default())
+    } else {
+        read_weights(arg.as_ref())
+    }
+}
+
+/// Loads the positions from a file of FENs, one per line
+///
+/// Blank lines are skipped
+fn load_positions(path: &Path) -> anyhow::Result<Vec<Board>> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read positions from {}", path.display()))?;
+
+    contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            let fen = line.split(';').next().unwrap_or_default().trim();
+            Board::from_str(fen)
+                .map_err(anyhow::Error::msg)
+                .with_context(|| format!("Invalid FEN on line {}", i + 1))
+        })
+        .collect()
+}
+
+/// Evaluation of `board` in centipawns from white's perspective, or `None` for a mate score
+fn white_centipawns(board: &Board, params: &EvalParams) -> Option<i32> {
+    let Score::Centipawns(cp) = eval_heuristic(board, params) else {
+        return None;
+    };
+
+    match board.side_to_move() {
+        Color::White => Some(cp as i32),
+        Color::Black => Some(-(cp as i32)),
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    ensure!(args.bucket > 0, "The bucket width must be positive");
+
+    let base = load_params(&args.base)?;
+    let new = load_params(&args.new)?;
+    let positions = load_positions(&args.positions)?;
+
+    // Mate scores have no meaningful difference in centipawns, so those positions are left out
+    let diffs: Vec<i32> = positions
+        .iter()
+        .filter_map(|board| Some(white_centipawns(board, &new)? - white_centipawns(board, &base)?))
+        .collect();
+    ensure!(
+        !diffs.is_empty(),
+        "No positions to compare in {}",
+        args.positions.display()
+    );
+
+    let min = *diffs.iter().min().unwrap(); // Not empty, as checked above
+    let max = *diffs.iter().max().unwrap();
+    let mean = diffs.iter().sum::<i32>() as f64 / diffs.len() as f64;
+    let mean_abs = diffs.iter().map(|diff| diff.abs()).sum::<i32>() as f64 / diffs.len() as f64;
+    let changed = diffs.iter().filter(|diff| **diff != 0).count();
+
+    println!(
+        "eval-diff {} -> {} over {} positions ({} skipped with mate scores)",
+        args.base,
+        args.new,
+        diffs.len(),
+        positions.len() - diffs.len()
+    );
+    println!(
+        "changed {}/{}, min {:+} cp, max {:+} cp, mean {:+.1} cp, mean absolute {:.1} cp",
+        changed,
+        diffs.len(),
+        min,
+        max,
+        mean,
+        mean_abs
+    );
+
+    // Buckets are aligned to multiples of the width, with one bucket for each width between the extremes
+    let first = min.div_euclid(args.bucket);
+    let last = max.div_euclid(args.bucket);
+    let mut counts = vec![0; (last - first + 1) as usize];
+    for diff in &diffs {
+        counts[(diff.div_euclid(args.bucket) - first) as usize] += 1;
+    }
+
+    let most = *counts.iter().max().unwrap(); // There is always at least one bucket
+    for (i, count) in counts.iter().enumerate() {
+        let low = (first + i as i32) * args.bucket;
+        let bar = "#".repeat((count * MAX_BAR_WIDTH).div_ceil(most));
+        println!(
+            "{:+6} to {:+6} cp: {:6} {}",
+            low,
+            low + args.bucket - 1,
+            count,
+            bar
+        );
+    }
+
+    Ok(())
+}
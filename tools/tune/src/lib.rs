@@ -0,0 +1,295 @@
+//! [Texel tuning](https://www.chessprogramming.org/Texel%27s_Tuning_Method) of the evaluation weights
+//!
+//! Each position is labeled with the result of the game it came from.
+//! The evaluation is mapped to an expected result with a sigmoid,
+//! and the weights are adjusted one at a time to minimize the mean-squared error against the actual results.
+
+use std::{fs, path::Path, str::FromStr};
+
+use anyhow::{Context, bail, ensure};
+use chess::{Board, Color, Piece};
+use patch::{
+    engine::evaluation::{EvalParams, eval_heuristic},
+    score::Score,
+};
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+
+/// A position, labeled with the result of its game
+#[derive(Debug, Clone)]
+pub struct Sample {
+    /// The position
+    pub board: Board,
+    /// Result of the game from white's perspective: 1.0 for a win, 0.5 for a draw, and 0.0 for a loss
+    pub result: f64,
+}
+
+/// Loads samples from a file of `FEN;result` lines, where the result is from white's perspective
+///
+/// Blank lines are skipped
+pub fn load_samples(path: &Path) -> anyhow::Result<Vec<Sample>> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read samples from {}", path.display()))?;
+
+    contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            parse_sample(line).with_context(|| format!("Invalid sample on line {}", i + 1))
+        })
+        .collect()
+}
+
+/// Parses a single `FEN;result` line
+fn parse_sample(line: &str) -> anyhow::Result<Sample> {
+    let Some((fen, result)) = line.split_once(';') else {
+        bail!("Expected FEN;result, got {}", line);
+    };
+
+    let board = Board::from_str(fen.trim()).map_err(anyhow::Error::msg)?;
+    let result: f64 = result.trim().parse().context("Invalid result")?;
+    ensure!(
+        [0.0, 0.5, 1.0].contains(&result),
+        "Expected a result of 1.0, 0.5, or 0.0, got {}",
+        result
+    );
+
+    Ok(Sample { board, result })
+}
+
+/// Expected result of `sample` from white's perspective, according to the evaluation with `params`
+///
+/// `k` scales the evaluation before the sigmoid, so that centipawns map onto results at the right rate
+fn expected_result(sample: &Sample, params: &EvalParams, k: f64) -> f64 {
+    let cp = match eval_heuristic(&sample.board, params) {
+        Score::Centipawns(cp) => cp as f64,
+        Score::Mate(m) if m > 0 => return perspective(1.0, &sample.board),
+        Score::Mate(_) => return perspective(0.0, &sample.board),
+    };
+
+    perspective(1.0 / (1.0 + 10f64.powf(-k * cp / 400.0)), &sample.board)
+}
+
+/// Converts an expected result for the player up to move on `board` into one for white
+fn perspective(result: f64, board: &Board) -> f64 {
+    match board.side_to_move() {
+        Color::White => result,
+        Color::Black => 1.0 - result,
+    }
+}
+
+/// Mean-squared error of the expected results with `params`, against the actual results of `samples`
+///
+/// The squared errors are computed in parallel, but summed in order,
+/// so that the same weights always give exactly the same error, however the work was split between threads
+pub fn mean_squared_error(samples: &[Sample], params: &EvalParams, k: f64) -> f64 {
+    let squared_errors: Vec<f64> = samples
+        .par_iter()
+        .map(|sample| (sample.result - expected_result(sample, params, k)).powi(2))
+        .collect();
+    let total: f64 = squared_errors.iter().sum();
+
+    total / samples.len() as f64
+}
+
+/// A single tunable weight in [`EvalParams`]
+enum Weight<'a> {
+    /// A material or piece table value
+    Table(&'a mut i16),
+    /// A term weight
+    Term(&'a mut i32),
+}
+
+impl Weight<'_> {
+    /// The current value of the weight
+    fn get(&self) -> i32 {
+        match self {
+            Weight::Table(weight) => **weight as i32,
+            Weight::Term(weight) => **weight,
+        }
+    }
+
+    /// Sets the weight to `value`, failing if it doesn't fit
+    fn set(&mut self, value: i32) -> anyhow::Result<()> {
+        match self {
+            Weight::Table(weight) => {
+                **weight = i16::try_from(value).context("Table weight out of range")?
+            }
+            Weight::Term(weight) => **weight = value,
+        }
+
+        Ok(())
+    }
+
+    /// Adds `delta` to the weight, returning whether the result fits (otherwise the weight is left unchanged)
+    fn add(&mut self, delta: i32) -> bool {
+        match self {
+            Weight::Table(weight) => {
+                let Some(sum) = i16::try_from(delta)
+                    .ok()
+                    .and_then(|delta| weight.checked_add(delta))
+                else {
+                    return false;
+                };
+                **weight = sum;
+            }
+            Weight::Term(weight) => {
+                let Some(sum) = weight.checked_add(delta) else {
+                    return false;
+                };
+                **weight = sum;
+            }
+        }
+
+        true
+    }
+}
+
+/// Every tunable weight in `params`, in a fixed order
+fn weights(params: &mut EvalParams) -> Vec<Weight<'_>> {
+    let mut weights = Vec::new();
+
+    // Both players always have a king, so its material value cancels out
+    for (mg, eg) in params.material[..Piece::King.to_index()].iter_mut() {
+        weights.extend([Weight::Table(mg), Weight::Table(eg)]);
+    }
+    for (mg, eg) in params.positions.iter_mut() {
+        weights.extend(mg.iter_mut().chain(eg.iter_mut()).map(Weight::Table));
+    }
+
+    let terms = [
+        &mut params.rook_behind_passer,
+        &mut params.rook_in_front_of_enemy_passer,
+        &mut params.trapped_bishop,
+        &mut params.trapped_knight,
+        &mut params.missing_shield_pawn,
+        &mut params.king_zone_attacker,
+        &mut params.doubled_pawn,
+        &mut params.isolated_pawn,
+        &mut params.bishop_pair,
+    ];
+    for (mg, eg) in terms
+        .into_iter()
+        .chain(params.mobility.iter_mut())
+        .chain(params.passed_pawn.iter_mut())
+    {
+        weights.extend([Weight::Term(mg), Weight::Term(eg)]);
+    }
+
+    weights
+}
+
+/// Runs one pass of local search over every weight in `params`, returning the new error
+///
+/// Each weight is nudged by `step` in either direction, and the change is kept only if it lowers the error.
+/// So, the error never increases from one iteration to the next.
+/// Nudges that would overflow the weight are skipped.
+pub fn tune_iteration(samples: &[Sample], params: &mut EvalParams, k: f64, step: i32) -> f64 {
+    let mut best = mean_squared_error(samples, params, k);
+
+    for i in 0..weights(params).len() {
+        for delta in [step, -step] {
+            if !weights(params)[i].add(delta) {
+                continue;
+            }
+
+            let error = mean_squared_error(samples, params, k);
+            if error < best {
+                best = error;
+                break;
+            }
+
+            // This always fits, as it restores the previous value
+            weights(params)[i].add(-delta);
+        }
+    }
+
+    best
+}
+
+/// Writes every weight in `params` as a weights file, with one weight per line in a fixed order
+///
+/// This is the format written by `tune --output`, and read back by [`read_weights`]
+pub fn write_weights(params: &EvalParams) -> String {
+    let mut params = params.clone();
+
+    weights(&mut params)
+        .iter()
+        .map(|weight| format!("{}\n", weight.get()))
+        .collect()
+}
+
+/// Reads a weights file written by [`write_weights`]
+pub fn read_weights(path: &Path) -> anyhow::Result<EvalParams> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read weights from {}", path.display()))?;
+    let values = contents
+        .split_whitespace()
+        .map(|value| value.parse::<i32>())
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("Invalid weight in {}", path.display()))?;
+
+    let mut params = EvalParams::default();
+    let mut weights = weights(&mut params);
+    ensure!(
+        values.len() == weights.len(),
+        "Expected {} weights in {}, got {}",
+        weights.len(),
+        path.display(),
+        values.len()
+    );
+    for (weight, value) in weights.iter_mut().zip(values) {
+        weight.set(value)?;
+    }
+
+    Ok(params)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The sample file shipped with the tuner
+    fn fixture() -> Vec<Sample> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/sample.txt");
+        load_samples(&path).unwrap()
+    }
+
+    #[test]
+    fn iteration_does_not_increase_error() {
+        let samples = fixture();
+        let mut params = EvalParams::default();
+        let error = mean_squared_error(&samples, &params, 1.0);
+
+        let new_error = tune_iteration(&samples, &mut params, 1.0, 1);
+        assert!(new_error <= error, "{} > {}", new_error, error);
+        assert_eq!(new_error, mean_squared_error(&samples, &params, 1.0));
+    }
+
+    #[test]
+    fn overflowing_steps_are_skipped() {
+        let mut weight = i16::MAX;
+        assert!(!Weight::Table(&mut weight).add(1));
+        assert!(!Weight::Table(&mut weight).add(i16::MAX as i32 + 1));
+        assert_eq!(weight, i16::MAX);
+
+        let mut weight = i32::MIN;
+        assert!(!Weight::Term(&mut weight).add(-1));
+        assert_eq!(weight, i32::MIN);
+
+        assert!(Weight::Term(&mut weight).add(1));
+        assert_eq!(weight, i32::MIN + 1);
+    }
+
+    #[test]
+    fn weights_round_trip() {
+        let mut params = EvalParams::default();
+        params.bishop_pair = (12, -34);
+        let path = std::env::temp_dir().join(format!("tune-weights-{}.txt", std::process::id()));
+
+        fs::write(&path, write_weights(&params)).unwrap();
+        let read = read_weights(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(read.unwrap(), params);
+    }
+}
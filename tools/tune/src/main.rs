@@ -0,0 +1,77 @@
+use std::{fs, path::PathBuf};
+
+use anyhow::{Context, ensure};
+use clap::Parser;
+use patch::engine::evaluation::EvalParams;
+use tune::{load_samples, mean_squared_error, tune_iteration, write_weights};
+
+/// Tunes the evaluation weights against a set of positions labeled with their game results
+///
+/// Samples are read from a file of `FEN;result` lines, where the result is 1.0, 0.5, or 0.0 from white's perspective.
+/// Tuning starts from the engine's current weights, and stops early once an iteration no longer improves them.
+#[derive(Parser, Debug)]
+struct Args {
+    /// File of labeled positions to tune against
+    #[arg(default_value = "tools/tune/fixtures/sample.txt")]
+    samples: PathBuf,
+
+    /// Maximum number of passes over the weights
+    #[arg(short, long, default_value_t = 10)]
+    iterations: usize,
+
+    /// Scaling constant of the sigmoid mapping centipawns to expected results
+    #[arg(short, long, default_value_t = 1.0)]
+    k: f64,
+
+    /// Amount each weight is nudged by when searching for a better value
+    #[arg(short, long, default_value_t = 1)]
+    step: i32,
+
+    /// File to write the tuned weights to (which `eval-diff` can read), instead of printing them
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+
+    let samples = load_samples(&args.samples)?;
+    ensure!(
+        !samples.is_empty(),
+        "No samples in {}",
+        args.samples.display()
+    );
+    println!("Loaded {} samples", samples.len());
+
+    let mut params = EvalParams::default();
+    let mut error = mean_squared_error(&samples, &params, args.k);
+    println!("Initial error: {:.6}", error);
+
+    for iteration in 1..=args.iterations {
+        let new_error = tune_iteration(&samples, &mut params, args.k, args.step);
+        println!("Iteration {}: error {:.6}", iteration, new_error);
+
+        // Changes are only kept when they lower the error, so anything else is a bug in the tuner
+        ensure!(
+            new_error <= error,
+            "Error increased from {:.6} to {:.6}",
+            error,
+            new_error
+        );
+        if new_error == error {
+            break;
+        }
+        error = new_error;
+    }
+
+    match args.output {
+        Some(path) => {
+            fs::write(&path, write_weights(&params))
+                .with_context(|| format!("Failed to write weights to {}", path.display()))?;
+            println!("Wrote tuned weights to {}", path.display());
+        }
+        None => println!("{:#?}", params),
+    }
+
+    Ok(())
+}